@@ -0,0 +1,192 @@
+use crate::genome::Genome;
+use crate::population::Population;
+
+use rand::prelude::*;
+use rand::thread_rng;
+
+use std::vec::Vec;
+
+// Which islands each island sends its migrants to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    // Island i sends to island i + 1, the last one to the first
+    Ring,
+    // Every island sends to every other island
+    Full,
+    // Every island sends to one other island picked at random each migration
+    Random,
+}
+
+// Several Populations evolved side by side, each with its own Settings and History.
+// Every migration_interval generations the fittest genomes of each island are copied
+// over to its neighbours, replacing their least fit citizens.
+pub struct Archipelago {
+    islands: Vec<Population>,
+    pub topology: Topology,
+    pub migration_interval: u64,
+    pub migrants: usize,
+    pub generations: u64,
+}
+
+impl Archipelago {
+    pub fn new(islands: Vec<Population>) -> Result<Self, &'static str> {
+        if islands.is_empty() {
+            return Err("An Archipelago needs at least one island");
+        }
+
+        let (inputs, outputs) = islands[0].io();
+        if islands.iter().any(|i| i.io() != (inputs, outputs)) {
+            return Err("Islands must all have the same number of inputs and outputs");
+        }
+
+        Ok(Self {
+            islands,
+            topology: Topology::Ring,
+            migration_interval: 10,
+            migrants: 1,
+            generations: 0,
+        })
+    }
+
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    // Generations between migrations, 0 disables migration
+    pub fn migration_interval(mut self, interval: u64) -> Self {
+        self.migration_interval = interval;
+        self
+    }
+
+    pub fn migrants(mut self, migrants: usize) -> Self {
+        self.migrants = migrants;
+        self
+    }
+
+    pub fn get_islands(&mut self) -> &mut Vec<Population> {
+        &mut self.islands
+    }
+
+    // Fittest genome found on any island
+    pub fn best_genome(&self) -> Option<&Genome> {
+        self.islands
+            .iter()
+            .filter_map(|i| i.best_genome.as_ref())
+            .max_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap())
+    }
+
+    // (from, to) pairs of islands migrants travel between
+    fn routes(&self) -> Vec<(usize, usize)> {
+        let n = self.islands.len();
+
+        if n < 2 {
+            return Vec::new();
+        }
+
+        match self.topology {
+            Topology::Ring => (0..n).map(|i| (i, (i + 1) % n)).collect(),
+            Topology::Full => (0..n)
+                .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+                .collect(),
+            Topology::Random => {
+                let mut rng = thread_rng();
+
+                (0..n)
+                    .map(|i| {
+                        let j = (0..n).filter(|&j| j != i).choose(&mut rng).unwrap();
+                        (i, j)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Migrants are picked from every island before any arrive, so a genome moves
+    // at most one hop per migration
+    pub fn migrate(&mut self) {
+        let emigrants = self
+            .islands
+            .iter()
+            .map(|i| i.emigrants(self.migrants))
+            .collect::<Vec<Vec<Genome>>>();
+
+        for (from, to) in self.routes() {
+            let (source, dest) = if from < to {
+                let (left, right) = self.islands.split_at_mut(to);
+                (&left[from], &mut right[0])
+            } else {
+                let (left, right) = self.islands.split_at_mut(from);
+                (&right[0], &mut left[to])
+            };
+
+            dest.immigrate(&emigrants[from], source);
+        }
+    }
+
+    // Call once every island has been evaluated
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn next_generation(&mut self) {
+        let interval = self.migration_interval;
+
+        if interval != 0 && self.generations > 0 && self.generations % interval == 0 {
+            self.migrate();
+        }
+
+        for island in self.islands.iter_mut() {
+            island.next_generation();
+        }
+
+        self.generations += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn migration_keeps_island_sizes() {
+        for topology in &[Topology::Ring, Topology::Full, Topology::Random] {
+            let islands = vec![
+                Population::new(Settings::new(2, 1, 20)),
+                Population::new(Settings::new(2, 1, 30).node_mut_rate(0.5)),
+                Population::new(Settings::new(2, 1, 25)),
+            ];
+            let mut archipelago = Archipelago::new(islands)
+                .unwrap()
+                .topology(*topology)
+                .migration_interval(1)
+                .migrants(3);
+
+            for _ in 0..5 {
+                for island in archipelago.get_islands() {
+                    for genome in island.get_citizens() {
+                        let output = genome.feed_forward(&vec![1., 0.]).unwrap();
+                        genome.add_fitness(output[0]);
+                    }
+                }
+
+                archipelago.next_generation();
+            }
+
+            let sizes = archipelago
+                .get_islands()
+                .iter_mut()
+                .map(|i| i.get_citizens().len())
+                .collect::<Vec<usize>>();
+            assert_eq!(sizes, vec![20, 30, 25]);
+        }
+    }
+
+    #[test]
+    fn mismatched_islands() {
+        let islands = vec![
+            Population::new(Settings::new(2, 1, 20)),
+            Population::new(Settings::new(3, 1, 20)),
+        ];
+
+        assert!(Archipelago::new(islands).is_err());
+    }
+}
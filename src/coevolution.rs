@@ -0,0 +1,210 @@
+use crate::genome::Genome;
+use crate::population::Population;
+
+use rand::prelude::*;
+use rand::thread_rng;
+
+use std::vec::Vec;
+
+// Which citizens of the other Populations each citizen is matched against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Matchup {
+    // Every citizen plays every citizen of every other Population
+    RoundRobin,
+    // Every citizen plays this many random citizens of each other Population
+    Sampled(usize),
+}
+
+// Adds the score of one match to a (total score, matches played) record
+fn credit(record: &mut (f64, u32), score: f64) {
+    record.0 += score;
+    record.1 += 1;
+}
+
+// Competitive coevolution between two or more Populations whose fitness comes from
+// matches against each other. A citizen's fitness is its mean score over its matches.
+pub struct Coevolution {
+    populations: Vec<Population>,
+    pub matchup: Matchup,
+    pub hall_of_fame: usize, // Past champions of each other Population every citizen plays
+    pub halls: Vec<Vec<Genome>>, // Champions of every past generation, per Population
+    pub generations: u64,
+}
+
+impl Coevolution {
+    pub fn new(populations: Vec<Population>) -> Result<Self, &'static str> {
+        if populations.len() < 2 {
+            return Err("Coevolution needs at least two populations");
+        }
+
+        let halls = vec![Vec::new(); populations.len()];
+
+        Ok(Self {
+            populations,
+            matchup: Matchup::RoundRobin,
+            hall_of_fame: 0,
+            halls,
+            generations: 0,
+        })
+    }
+
+    pub fn matchup(mut self, matchup: Matchup) -> Self {
+        self.matchup = matchup;
+        self
+    }
+
+    pub fn hall_of_fame(mut self, opponents: usize) -> Self {
+        self.hall_of_fame = opponents;
+        self
+    }
+
+    pub fn get_populations(&mut self) -> &mut Vec<Population> {
+        &mut self.populations
+    }
+
+    // Plays every scheduled match and adds each citizen's mean score to its fitness.
+    // play receives both sides of a match and returns their scores, which should not
+    // be negative. Only the current citizen is credited for matches against the hall
+    // of fame.
+    pub fn evaluate<F>(&mut self, mut play: F)
+    where
+        F: FnMut(&Genome, &Genome) -> (f64, f64),
+    {
+        let mut rng = thread_rng();
+
+        let citizens = self
+            .populations
+            .iter_mut()
+            .map(|p| &*p.get_citizens())
+            .collect::<Vec<&Vec<Genome>>>();
+
+        // (total score, matches played) of every citizen
+        let mut scores = citizens
+            .iter()
+            .map(|c| vec![(0., 0); c.len()])
+            .collect::<Vec<Vec<(f64, u32)>>>();
+
+        for p in 0..citizens.len() {
+            for q in 0..citizens.len() {
+                if p == q {
+                    continue;
+                }
+
+                match self.matchup {
+                    // Each pair of Populations is only scheduled once
+                    Matchup::RoundRobin if p < q => {
+                        for (i, a) in citizens[p].iter().enumerate() {
+                            for (j, b) in citizens[q].iter().enumerate() {
+                                let (score_a, score_b) = play(a, b);
+                                credit(&mut scores[p][i], score_a);
+                                credit(&mut scores[q][j], score_b);
+                            }
+                        }
+                    }
+                    Matchup::RoundRobin => {}
+                    Matchup::Sampled(n) => {
+                        for (i, a) in citizens[p].iter().enumerate() {
+                            let opponents = (0..citizens[q].len()).choose_multiple(&mut rng, n);
+
+                            for j in opponents {
+                                let (score_a, score_b) = play(a, &citizens[q][j]);
+                                credit(&mut scores[p][i], score_a);
+                                credit(&mut scores[q][j], score_b);
+                            }
+                        }
+                    }
+                }
+
+                for (i, a) in citizens[p].iter().enumerate() {
+                    for champ in self.halls[q].choose_multiple(&mut rng, self.hall_of_fame) {
+                        credit(&mut scores[p][i], play(a, champ).0);
+                    }
+                }
+            }
+        }
+
+        for (population, scores) in self.populations.iter_mut().zip(scores) {
+            for (genome, (score, matches)) in population.get_citizens().iter_mut().zip(scores) {
+                if matches > 0 {
+                    genome.add_fitness(score / matches as f64);
+                }
+            }
+        }
+    }
+
+    // Enters the champion of every Population into its hall of fame and breeds the
+    // next generation of each
+    pub fn next_generation(&mut self) {
+        for (population, hall) in self.populations.iter_mut().zip(self.halls.iter_mut()) {
+            hall.extend(population.emigrants(1));
+            population.next_generation();
+        }
+
+        self.generations += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+
+    fn play(a: &Genome, b: &Genome) -> (f64, f64) {
+        let a = a.feed_forward(&vec![1., 0.]).unwrap()[0];
+        let b = b.feed_forward(&vec![1., 0.]).unwrap()[0];
+
+        if a > b {
+            (1., 0.)
+        } else {
+            (0., 1.)
+        }
+    }
+
+    #[test]
+    fn matches_assign_fitness() {
+        for matchup in &[Matchup::RoundRobin, Matchup::Sampled(3)] {
+            let populations = vec![
+                Population::new(Settings::new(2, 1, 10)),
+                Population::new(Settings::new(2, 1, 15)),
+            ];
+            let mut coevolution = Coevolution::new(populations)
+                .unwrap()
+                .matchup(*matchup)
+                .hall_of_fame(2);
+
+            for _ in 0..3 {
+                coevolution.evaluate(play);
+
+                for population in coevolution.get_populations() {
+                    assert!(population
+                        .get_citizens()
+                        .iter()
+                        .all(|g| (0. ..=1.).contains(&g.fitness)));
+                }
+
+                coevolution.next_generation();
+            }
+
+            assert!(coevolution.halls.iter().all(|h| h.len() == 3));
+        }
+    }
+
+    #[test]
+    fn round_robin_is_zero_sum() {
+        let populations = vec![
+            Population::new(Settings::new(2, 1, 4)),
+            Population::new(Settings::new(2, 1, 4)),
+        ];
+        let mut coevolution = Coevolution::new(populations).unwrap();
+
+        coevolution.evaluate(play);
+
+        let total = coevolution
+            .get_populations()
+            .iter_mut()
+            .flat_map(|p| p.get_citizens().iter().map(|g| g.fitness))
+            .sum::<f64>();
+        // 16 matches, each worth 1 split over the 4 matches of every citizen
+        assert!((total - 4.).abs() < 1e-9);
+    }
+}
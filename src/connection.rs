@@ -42,15 +42,13 @@ impl Clone for Connection {
 
 impl Connection {
     pub fn new(innov: u32, from: u32, to: u32, weight: f64, enabled: bool) -> Self {
-        let conn = Self {
+        Self {
             innov,
             from,
             to,
             weight,
             enabled,
-        };
-
-        conn
+        }
     }
 
     pub fn enable(&mut self) {
@@ -66,12 +64,7 @@ impl Connection {
 
         if rng.gen::<f64>() < sets.wt_shift_rate {
             self.weight += Normal::new(0., 0.04).unwrap().sample(&mut rng);
-
-            if self.weight < -1. {
-                self.weight = -1.;
-            } else if self.weight > 1. {
-                self.weight = 1.
-            }
+            self.weight = self.weight.clamp(-1., 1.);
         } else {
             self.weight = rng.gen::<f64>() * 2. - 1.;
         }
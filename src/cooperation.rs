@@ -0,0 +1,203 @@
+use crate::genome::Genome;
+use crate::population::Population;
+
+use rand::prelude::*;
+use rand::thread_rng;
+
+use std::vec::Vec;
+
+// How the scores of the teams a citizen took part in become its fitness
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Credit {
+    // Mean score of its teams, as in ESP
+    Mean,
+    // Score of its best team, so a good module isn't punished for poor teammates
+    Max,
+}
+
+// Cooperative coevolution where every Population evolves one module of a composite
+// network. Teams are assembled from one citizen of each Population, evaluated as a
+// whole, and their score credited back to every member.
+pub struct Cooperation {
+    populations: Vec<Population>,
+    pub trials: usize, // Teams every citizen takes part in per generation
+    pub credit: Credit,
+    pub best_team: Option<Vec<Genome>>,
+    pub best_fitness: f64,
+    pub generations: u64,
+}
+
+impl Cooperation {
+    pub fn new(populations: Vec<Population>) -> Result<Self, &'static str> {
+        if populations.is_empty() {
+            return Err("Cooperation needs at least one population");
+        }
+
+        Ok(Self {
+            populations,
+            trials: 10,
+            credit: Credit::Mean,
+            best_team: None,
+            best_fitness: 0.,
+            generations: 0,
+        })
+    }
+
+    pub fn trials(mut self, trials: usize) -> Self {
+        self.trials = trials;
+        self
+    }
+
+    pub fn credit(mut self, credit: Credit) -> Self {
+        self.credit = credit;
+        self
+    }
+
+    pub fn get_populations(&mut self) -> &mut Vec<Population> {
+        &mut self.populations
+    }
+
+    // Feeds the input through the members in order, each one's outputs being the
+    // inputs of the next
+    pub fn feed_forward(team: &[&Genome], input: &[f64]) -> Result<Vec<f64>, &'static str> {
+        let mut values = input.to_vec();
+
+        for member in team {
+            values = member.feed_forward(&values)?;
+        }
+
+        Ok(values)
+    }
+
+    // Scores trials rounds of random teams with evaluate, which receives one member
+    // from every Population in order and should return a non negative score. Every
+    // citizen takes part in one team per round.
+    pub fn evaluate<F>(&mut self, mut evaluate: F)
+    where
+        F: FnMut(&[&Genome]) -> f64,
+    {
+        let mut rng = thread_rng();
+
+        let citizens = self
+            .populations
+            .iter_mut()
+            .map(|p| &*p.get_citizens())
+            .collect::<Vec<&Vec<Genome>>>();
+
+        if citizens.iter().any(|c| c.is_empty()) {
+            return;
+        }
+
+        let teams = citizens.iter().map(|c| c.len()).max().unwrap();
+
+        // (total score, best score, teams) of every citizen
+        let mut scores = citizens
+            .iter()
+            .map(|c| vec![(0., 0., 0); c.len()])
+            .collect::<Vec<Vec<(f64, f64, u32)>>>();
+
+        let mut best = None;
+
+        for _ in 0..self.trials {
+            // Smaller Populations wrap around, so every citizen is in at least one team
+            let orders = citizens
+                .iter()
+                .map(|c| {
+                    let mut order = (0..c.len()).collect::<Vec<usize>>();
+                    order.shuffle(&mut rng);
+                    order
+                })
+                .collect::<Vec<Vec<usize>>>();
+
+            for t in 0..teams {
+                let members = orders
+                    .iter()
+                    .map(|o| o[t % o.len()])
+                    .collect::<Vec<usize>>();
+                let team = citizens
+                    .iter()
+                    .zip(&members)
+                    .map(|(c, &m)| &c[m])
+                    .collect::<Vec<&Genome>>();
+
+                let score = evaluate(&team);
+
+                for (p, &m) in members.iter().enumerate() {
+                    let record = &mut scores[p][m];
+                    record.0 += score;
+                    record.1 = f64::max(record.1, score);
+                    record.2 += 1;
+                }
+
+                if score > self.best_fitness {
+                    self.best_fitness = score;
+                    best = Some(team.into_iter().cloned().collect::<Vec<Genome>>());
+                }
+            }
+        }
+
+        if best.is_some() {
+            self.best_team = best;
+        }
+
+        for (population, scores) in self.populations.iter_mut().zip(scores) {
+            for (genome, (total, max, teams)) in population.get_citizens().iter_mut().zip(scores) {
+                if teams == 0 {
+                    continue;
+                }
+
+                genome.add_fitness(match self.credit {
+                    Credit::Mean => total / teams as f64,
+                    Credit::Max => max,
+                });
+            }
+        }
+    }
+
+    pub fn next_generation(&mut self) {
+        for population in self.populations.iter_mut() {
+            population.next_generation();
+        }
+
+        self.generations += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn teams_credit_members() {
+        for credit in &[Credit::Mean, Credit::Max] {
+            let populations = vec![
+                Population::new(Settings::new(2, 3, 10)),
+                Population::new(Settings::new(3, 1, 15)),
+            ];
+            let mut cooperation = Cooperation::new(populations)
+                .unwrap()
+                .trials(3)
+                .credit(*credit);
+
+            for _ in 0..3 {
+                cooperation.evaluate(|team| Cooperation::feed_forward(team, &[1., 0.]).unwrap()[0]);
+
+                for population in cooperation.get_populations() {
+                    assert!(population
+                        .get_citizens()
+                        .iter()
+                        .all(|g| g.fitness > 0. && g.fitness < 1.));
+                }
+
+                cooperation.next_generation();
+            }
+
+            let best = cooperation.best_team.as_ref().unwrap();
+            let team = best.iter().collect::<Vec<&Genome>>();
+            let output = Cooperation::feed_forward(&team, &[1., 0.]).unwrap()[0];
+
+            assert_eq!(output, cooperation.best_fitness);
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use crate::genome::Genome;
+use crate::hyperneat::Substrate;
+use crate::network::Network;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::vec::Vec;
+
+// Square region of the quadtree, centered on (x, y) and spanning width in every
+// direction. The root covers the whole [-1, 1] x [-1, 1] substrate.
+struct QuadPoint {
+    x: f64,
+    y: f64,
+    width: f64,
+    level: u32,
+    weight: f64,
+    children: Vec<QuadPoint>,
+}
+
+impl QuadPoint {
+    fn new(x: f64, y: f64, width: f64, level: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            level,
+            weight: 0.,
+            children: Vec::new(),
+        }
+    }
+
+    fn leaf_weights(&self, weights: &mut Vec<f64>) {
+        if self.children.is_empty() {
+            weights.push(self.weight);
+        } else {
+            self.children.iter().for_each(|c| c.leaf_weights(weights));
+        }
+    }
+
+    fn variance(&self) -> f64 {
+        let mut weights = Vec::<f64>::new();
+        self.leaf_weights(&mut weights);
+
+        let len = weights.len() as f64;
+        let mean = weights.iter().sum::<f64>() / len;
+
+        weights.iter().fold(0., |acc, w| acc + (w - mean).powi(2)) / len
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Endpoint {
+    Input(usize),
+    Hidden(usize),
+    Output(usize),
+}
+
+// Phenotype of an evolvable substrate along with the hidden node positions that
+// were discovered for it
+pub struct EsNetwork {
+    pub network: Network,
+    pub hidden: Vec<Vec<f64>>,
+}
+
+// Evolvable-substrate HyperNEAT. Only the input and output coordinates of the
+// substrate are given, hidden nodes are placed wherever the CPPN's weight pattern
+// carries the most information, found by quadtree decomposition of the variance
+// of the CPPN outputs. Coordinates are two dimensional and lie in [-1, 1].
+pub struct EsSubstrate {
+    pub substrate: Substrate, // Inputs, outputs and expression rules
+    pub initial_depth: u32,
+    pub max_depth: u32,
+    pub division_threshold: f64,
+    pub variance_threshold: f64,
+    pub band_threshold: f64,
+    pub iteration_level: u32, // Rounds of hidden to hidden node discovery
+}
+
+impl EsSubstrate {
+    pub fn new(substrate: Substrate) -> Self {
+        Self {
+            substrate,
+            initial_depth: 3,
+            max_depth: 4,
+            division_threshold: 0.03,
+            variance_threshold: 0.03,
+            band_threshold: 0.3,
+            iteration_level: 1,
+        }
+    }
+
+    pub fn initial_depth(mut self, depth: u32) -> Self {
+        self.initial_depth = depth;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: u32) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn division_threshold(mut self, threshold: f64) -> Self {
+        self.division_threshold = threshold;
+        self
+    }
+
+    pub fn variance_threshold(mut self, threshold: f64) -> Self {
+        self.variance_threshold = threshold;
+        self
+    }
+
+    pub fn band_threshold(mut self, threshold: f64) -> Self {
+        self.band_threshold = threshold;
+        self
+    }
+
+    pub fn iteration_level(mut self, level: u32) -> Self {
+        self.iteration_level = level;
+        self
+    }
+
+    // Raw CPPN weight between the source and the point, in the direction given
+    fn weight(
+        cppn: &Genome,
+        source: &[f64],
+        x: f64,
+        y: f64,
+        outgoing: bool,
+    ) -> Result<f64, &'static str> {
+        if outgoing {
+            Substrate::cppn_weight(cppn, source, &[x, y])
+        } else {
+            Substrate::cppn_weight(cppn, &[x, y], source)
+        }
+    }
+
+    // Keeps dividing the substrate where the CPPN's weight pattern varies
+    fn division(
+        &self,
+        cppn: &Genome,
+        source: &[f64],
+        outgoing: bool,
+    ) -> Result<QuadPoint, &'static str> {
+        let mut root = QuadPoint::new(0., 0., 1., 1);
+        let mut queue = VecDeque::<&mut QuadPoint>::new();
+        queue.push_back(&mut root);
+
+        while let Some(point) = queue.pop_front() {
+            let half = point.width / 2.;
+
+            for &(dx, dy) in &[(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)] {
+                let mut child = QuadPoint::new(
+                    point.x + dx * half,
+                    point.y + dy * half,
+                    half,
+                    point.level + 1,
+                );
+                child.weight = Self::weight(cppn, source, child.x, child.y, outgoing)?;
+                point.children.push(child);
+            }
+
+            if point.level < self.initial_depth
+                || (point.level < self.max_depth && point.variance() > self.division_threshold)
+            {
+                queue.extend(point.children.iter_mut());
+            }
+        }
+
+        Ok(root)
+    }
+
+    // Collects the points of low variance regions that stand out from their
+    // neighbours by more than the band threshold
+    fn extract(
+        &self,
+        cppn: &Genome,
+        source: &[f64],
+        point: &QuadPoint,
+        outgoing: bool,
+        found: &mut Vec<(f64, f64)>,
+    ) -> Result<(), &'static str> {
+        for child in &point.children {
+            if !child.children.is_empty() && child.variance() >= self.variance_threshold {
+                self.extract(cppn, source, child, outgoing, found)?;
+                continue;
+            }
+
+            let w = point.width;
+            let diff = |x: f64, y: f64| -> Result<f64, &'static str> {
+                Ok((child.weight - Self::weight(cppn, source, x, y, outgoing)?).abs())
+            };
+
+            let left = diff(child.x - w, child.y)?;
+            let right = diff(child.x + w, child.y)?;
+            let top = diff(child.x, child.y - w)?;
+            let bottom = diff(child.x, child.y + w)?;
+
+            if left.min(right).max(top.min(bottom)) > self.band_threshold {
+                found.push((child.x, child.y));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn discover(
+        &self,
+        cppn: &Genome,
+        source: &[f64],
+        outgoing: bool,
+    ) -> Result<Vec<(f64, f64)>, &'static str> {
+        let root = self.division(cppn, source, outgoing)?;
+        let mut found = Vec::new();
+        self.extract(cppn, source, &root, outgoing, &mut found)?;
+
+        Ok(found)
+    }
+
+    pub fn build(&self, cppn: &Genome) -> Result<EsNetwork, &'static str> {
+        let substrate = &self.substrate;
+
+        if substrate
+            .inputs
+            .iter()
+            .chain(&substrate.outputs)
+            .any(|c| c.len() != 2)
+        {
+            return Err("Evolvable substrates must be two dimensional");
+        }
+
+        let mut hidden = Vec::<(f64, f64)>::new();
+        let mut hidden_ids = HashMap::<(u64, u64), usize>::new();
+        let mut conns = HashSet::<(Endpoint, Endpoint)>::new();
+
+        let mut hidden_id = |hidden: &mut Vec<(f64, f64)>, p: (f64, f64)| -> (usize, bool) {
+            let len = hidden.len();
+            let id = *hidden_ids
+                .entry((p.0.to_bits(), p.1.to_bits()))
+                .or_insert(len);
+            if id == len {
+                hidden.push(p);
+            }
+            (id, id == len)
+        };
+
+        let mut unexplored = Vec::<usize>::new();
+
+        for (i, input) in substrate.inputs.iter().enumerate() {
+            for p in self.discover(cppn, input, true)? {
+                let (h, new) = hidden_id(&mut hidden, p);
+                if new {
+                    unexplored.push(h);
+                }
+                conns.insert((Endpoint::Input(i), Endpoint::Hidden(h)));
+            }
+        }
+
+        for _ in 0..self.iteration_level {
+            let mut next = Vec::<usize>::new();
+
+            for h in unexplored {
+                let source = [hidden[h].0, hidden[h].1];
+
+                for p in self.discover(cppn, &source, true)? {
+                    let (to, new) = hidden_id(&mut hidden, p);
+                    if new {
+                        next.push(to);
+                    }
+                    if to != h {
+                        conns.insert((Endpoint::Hidden(h), Endpoint::Hidden(to)));
+                    }
+                }
+            }
+
+            unexplored = next;
+        }
+
+        for (o, output) in substrate.outputs.iter().enumerate() {
+            for p in self.discover(cppn, output, false)? {
+                if let Some(&h) = hidden_ids.get(&(p.0.to_bits(), p.1.to_bits())) {
+                    conns.insert((Endpoint::Hidden(h), Endpoint::Output(o)));
+                }
+            }
+        }
+
+        let useful = Self::useful_hidden(hidden.len(), &conns);
+
+        // Hidden nodes are laid out from the input side of the substrate towards the
+        // output side. Connections leading backwards are dropped to stay feed forward.
+        let mut order = (0..hidden.len())
+            .filter(|h| useful[*h])
+            .collect::<Vec<usize>>();
+        order.sort_by(|&a, &b| {
+            (hidden[a].1, hidden[a].0)
+                .partial_cmp(&(hidden[b].1, hidden[b].0))
+                .unwrap()
+        });
+
+        let inputs = substrate.inputs.len();
+        let mut index = HashMap::<Endpoint, usize>::new();
+        (0..inputs).for_each(|i| {
+            index.insert(Endpoint::Input(i), i);
+        });
+        order.iter().enumerate().for_each(|(i, &h)| {
+            index.insert(Endpoint::Hidden(h), inputs + i);
+        });
+        let first_output = inputs + order.len();
+        (0..substrate.outputs.len()).for_each(|o| {
+            index.insert(Endpoint::Output(o), first_output + o);
+        });
+
+        let nodes = first_output + substrate.outputs.len();
+        let mut network = Network::new(inputs, nodes, (first_output..nodes).collect());
+
+        let coord = |e: Endpoint| -> Vec<f64> {
+            match e {
+                Endpoint::Input(i) => substrate.inputs[i].clone(),
+                Endpoint::Hidden(h) => vec![hidden[h].0, hidden[h].1],
+                Endpoint::Output(o) => substrate.outputs[o].clone(),
+            }
+        };
+
+        let mut conns = conns.into_iter().collect::<Vec<(Endpoint, Endpoint)>>();
+        conns.sort_by_key(|(from, to)| (index.get(from), index.get(to)));
+
+        for (from, to) in conns {
+            let (from_idx, to_idx) = match (index.get(&from), index.get(&to)) {
+                (Some(&f), Some(&t)) if f < t => (f, t),
+                _ => continue,
+            };
+
+            if let Some(weight) = substrate.query(cppn, &coord(from), &coord(to))? {
+                network.add_conn(from_idx, to_idx, weight)?;
+            }
+        }
+
+        Ok(EsNetwork {
+            network,
+            hidden: order
+                .iter()
+                .map(|&h| vec![hidden[h].0, hidden[h].1])
+                .collect(),
+        })
+    }
+
+    // Hidden nodes that are both reachable from an input and lead to an output
+    fn useful_hidden(len: usize, conns: &HashSet<(Endpoint, Endpoint)>) -> Vec<bool> {
+        let reach = |forward: bool| {
+            let mut seen = vec![false; len];
+            let mut stack = conns
+                .iter()
+                .filter_map(|&(from, to)| match (forward, from, to) {
+                    (true, Endpoint::Input(_), Endpoint::Hidden(h)) => Some(h),
+                    (false, Endpoint::Hidden(h), Endpoint::Output(_)) => Some(h),
+                    _ => None,
+                })
+                .collect::<Vec<usize>>();
+
+            while let Some(h) = stack.pop() {
+                if seen[h] {
+                    continue;
+                }
+                seen[h] = true;
+
+                for &(from, to) in conns {
+                    match (forward, from, to) {
+                        (true, Endpoint::Hidden(a), Endpoint::Hidden(b)) if a == h => stack.push(b),
+                        (false, Endpoint::Hidden(a), Endpoint::Hidden(b)) if b == h => {
+                            stack.push(a)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            seen
+        };
+
+        let from_inputs = reach(true);
+        let to_outputs = reach(false);
+
+        (0..len).map(|h| from_inputs[h] && to_outputs[h]).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn discovers_hidden_nodes() {
+        let substrate = Substrate::new(
+            vec![vec![-1., -1.], vec![0., -1.], vec![1., -1.]],
+            vec![vec![0., 1.]],
+        )
+        .weight_threshold(0.);
+        let es = EsSubstrate::new(substrate).band_threshold(0.);
+        let mut cppn = Genome::new(4, 1, false);
+
+        for conn in &mut cppn.conns {
+            conn.weight = 1.;
+        }
+
+        let res = es.build(&cppn).unwrap();
+
+        assert!(!res.hidden.is_empty());
+        assert_eq!(res.network.nodes(), 4 + res.hidden.len());
+        assert!(res.hidden.iter().all(|c| c.len() == 2));
+        assert_eq!(
+            res.network.feed_forward(&vec![0., 1., 0.]).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_other_dimensions() {
+        let substrate = Substrate::new(vec![vec![0., 0., 0.]], vec![vec![0., 1., 0.]]);
+        let cppn = Genome::new(6, 1, false);
+
+        assert!(EsSubstrate::new(substrate).build(&cppn).is_err());
+    }
+}
@@ -1,24 +1,64 @@
 use crate::connection::Connection;
 use crate::history::History;
+use crate::lineage::Mutation;
 use crate::node::Node;
-use crate::settings::Settings;
+use crate::settings::{CrossoverMode, GeneNormalization, Settings};
 
 use rand::prelude::*;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
 use std::clone::Clone;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::vec::Vec;
 
 // Main Genome Class
 pub struct Genome {
-    inputs: u32,                // Number of Inputs
-    outputs: u32,               // Number of Outputs
-    nodes: Vec<Node>,           // Vector of Nodes
-    pub conns: Vec<Connection>, // Vector of Connections
-    pub fitness: f64,           // Fitness of this Genome
+    inputs: u32,                  // Number of Inputs
+    outputs: u32,                 // Number of Outputs
+    nodes: Vec<Node>,             // Vector of Nodes
+    pub conns: Vec<Connection>,   // Vector of Connections
+    pub fitness: f64,             // Fitness of this Genome
+    pub adjusted_fitness: f64,    // Selection score, shared within its Species
+    pub id: u64,                  // Unique, increasing ID assigned by the Population
+    pub parents: Vec<u64>,        // IDs of the Genomes this one was bred from
+    pub mutations: Vec<Mutation>, // Mutations applied after breeding
+    pub behavior: Vec<f64>,       // Behavior characterization used by novelty search
+    pub novelty: f64,             // Novelty of the behavior in its generation
+    pub objectives: Vec<f64>,     // Objectives to maximize in multi-objective evolution
+    pub born: u64,                // Population tick the Genome was born at
+}
+
+// Breakdown of the genes two Genomes do and don't have in common
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeneComparison {
+    pub disjoint: usize,
+    pub excess: usize,
+    pub matching: usize,
+    pub mean_weight_diff: f64,
+    pub genes: usize, // Gene count of the larger Genome
+}
+
+impl GeneComparison {
+    // Compatibility distance as described in the NEAT paper. Unless disabled in the
+    // Settings, Genomes sharing no genes at all are considered infinitely far apart.
+    pub fn delta(&self, sets: &Settings) -> f64 {
+        if self.matching == 0 && sets.require_matching_genes {
+            return f64::INFINITY;
+        }
+
+        let n = match sets.gene_normalization {
+            GeneNormalization::Cutoff(cutoff) if self.genes < cutoff => 1.,
+            GeneNormalization::Never => 1.,
+            _ => self.genes.max(1) as f64,
+        };
+
+        (sets.disjoint_coeff * self.disjoint as f64 / n)
+            + (sets.excess_coeff * self.excess as f64 / n)
+            + (sets.weight_coeff * self.mean_weight_diff)
+    }
 }
 
 impl fmt::Debug for Genome {
@@ -49,6 +89,14 @@ impl Genome {
             nodes: Vec::with_capacity((inputs + outputs + 1) as usize),
             conns: Vec::with_capacity(((inputs + 1) * outputs) as usize),
             fitness: 0.,
+            adjusted_fitness: 0.,
+            id: 0,
+            parents: Vec::new(),
+            mutations: Vec::new(),
+            behavior: Vec::new(),
+            novelty: 0.,
+            objectives: Vec::new(),
+            born: 0,
         };
 
         let mut dy = 1. / (inputs + 1) as f64;
@@ -94,17 +142,48 @@ impl Genome {
         self.fitness = if fitness < 0. { 0. } else { fitness };
     }
 
-    pub fn feed_forward(&mut self, input: &Vec<f64>) -> Result<Vec<f64>, &'static str> {
+    pub fn node_innovs(&self) -> impl Iterator<Item = u32> + '_ {
+        self.nodes.iter().map(|n| n.innov)
+    }
+
+    pub fn enabled_conns(&self) -> usize {
+        self.conns.iter().filter(|c| c.enabled).count()
+    }
+
+    pub fn hidden_nodes(&self) -> usize {
+        self.nodes.len() - (self.inputs + self.outputs + 1) as usize
+    }
+
+    // Enabled connections and hidden nodes, used to break fitness ties
+    pub fn size(&self) -> usize {
+        self.enabled_conns() + self.hidden_nodes()
+    }
+
+    pub fn set_behavior(&mut self, behavior: Vec<f64>) {
+        self.behavior = behavior;
+    }
+
+    pub fn add_objective(&mut self, objective: f64) {
+        self.objectives.push(objective);
+    }
+
+    pub fn reset_fitness(&mut self) {
+        self.fitness = 0.;
+        self.adjusted_fitness = 0.;
+        self.behavior.clear();
+        self.novelty = 0.;
+        self.objectives.clear();
+    }
+
+    pub fn feed_forward(&self, input: &Vec<f64>) -> Result<Vec<f64>, &'static str> {
         if input.len() != self.inputs as usize {
             return Err("Provided input size doesn't match Genome input size");
         }
 
         let mut node_vals = HashMap::<u32, f64>::new();
 
-        let mut i = 1;
-        for val in input {
+        for (i, val) in (1..).zip(input) {
             node_vals.insert(i, *val);
-            i += 1;
         }
 
         node_vals.insert(self.inputs + 1, 1.);
@@ -131,15 +210,95 @@ impl Genome {
             .collect())
     }
 
+    pub fn compare(&self, other: &Self) -> GeneComparison {
+        let max1 = self.conns.iter().map(|c| c.innov).max().unwrap_or(0);
+        let max2 = other.conns.iter().map(|c| c.innov).max().unwrap_or(0);
+
+        let (mut genes1, mut genes2) = if max1 > max2 {
+            (self.conns.iter(), other.conns.iter())
+        } else {
+            (other.conns.iter(), self.conns.iter())
+        };
+
+        let mut p1 = genes1.next();
+        let mut p2 = genes2.next();
+
+        let mut disjoint = 0;
+        let mut excess = 0;
+        let mut matching = 0;
+        let mut weight_difference = 0.;
+
+        while let Some(g1) = p1 {
+            match p2 {
+                Some(g2) if g1.innov == g2.innov => {
+                    matching += 1;
+                    weight_difference += (g1.weight - g2.weight).abs();
+                    p1 = genes1.next();
+                    p2 = genes2.next();
+                }
+                Some(g2) if g2.innov > g1.innov => {
+                    disjoint += 1;
+                    p1 = genes1.next();
+                }
+                Some(_) => {
+                    disjoint += 1;
+                    p2 = genes2.next();
+                }
+                None => {
+                    excess += 1;
+                    p1 = genes1.next();
+                }
+            }
+        }
+
+        GeneComparison {
+            disjoint,
+            excess,
+            matching,
+            mean_weight_diff: if matching == 0 {
+                0.
+            } else {
+                weight_difference / matching as f64
+            },
+            genes: self.conns.len().max(other.conns.len()),
+        }
+    }
+
+    pub fn distance(&self, other: &Self, sets: &Settings) -> f64 {
+        self.compare(other).delta(sets)
+    }
+
+    // Maps every node to a new innovation number and gives each connection the
+    // innovation number of its new ends
+    pub fn renumber(&mut self, node: impl Fn(u32) -> u32, mut conn: impl FnMut(u32, u32) -> u32) {
+        for n in self.nodes.iter_mut() {
+            n.innov = node(n.innov);
+        }
+
+        for c in self.conns.iter_mut() {
+            c.from = node(c.from);
+            c.to = node(c.to);
+            c.innov = conn(c.from, c.to);
+        }
+
+        self.conns.sort_unstable_by_key(|c| c.innov);
+    }
+
     pub fn mutate(&mut self, hist: &mut History, sets: &Settings) {
         let mut rng = thread_rng();
+        let mut weights = 0;
 
         self.conns.iter_mut().for_each(|c| {
             if rng.gen::<f64>() < sets.wt_mut_rate {
                 c.mutate_weight(sets);
+                weights += 1;
             }
         });
 
+        if weights > 0 {
+            self.mutations.push(Mutation::Weights(weights));
+        }
+
         if rng.gen::<f64>() < sets.conn_mut_rate {
             self.add_conn(hist);
         }
@@ -148,7 +307,7 @@ impl Genome {
             self.add_node(hist);
         }
 
-        self.conns.sort_unstable_by(|a, b| a.innov.cmp(&b.innov));
+        self.conns.sort_unstable_by_key(|c| c.innov);
     }
 
     fn add_conn(&mut self, hist: &mut History) {
@@ -175,11 +334,11 @@ impl Genome {
                     })
                     .collect::<Vec<&Node>>();
 
-                to_nodes.len() > 0
+                !to_nodes.is_empty()
             })
             .collect::<Vec<&Node>>();
 
-        if from_node_pool.len() == 0 {
+        if from_node_pool.is_empty() {
             return;
         }
 
@@ -210,6 +369,7 @@ impl Genome {
             true,
         );
 
+        self.mutations.push(Mutation::AddConnection(innov));
         self.conns.push(new_conn);
     }
 
@@ -218,7 +378,7 @@ impl Genome {
 
         let conn_to_mutate = self.conns.iter_mut().choose(&mut rng).unwrap();
 
-        let details = hist.mutate_node(&conn_to_mutate);
+        let details = hist.mutate_node(conn_to_mutate);
 
         let from_node = self
             .nodes
@@ -246,6 +406,7 @@ impl Genome {
         );
 
         conn_to_mutate.disable();
+        self.mutations.push(Mutation::AddNode(new_node.innov));
         self.nodes.push(new_node);
         self.conns.push(in_conn);
         self.conns.push(out_conn);
@@ -254,8 +415,25 @@ impl Genome {
             .sort_unstable_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
     }
 
+    // Orders two genomes by selection score. On ties the smaller one is the fitter
+    // when size_tiebreak is set, as when the Population is sorted.
+    fn compare_fitness(&self, other: &Self, sets: &Settings) -> Ordering {
+        let order = self
+            .adjusted_fitness
+            .partial_cmp(&other.adjusted_fitness)
+            .unwrap();
+
+        if sets.size_tiebreak {
+            order.then_with(|| other.size().cmp(&self.size()))
+        } else {
+            order
+        }
+    }
+
     pub fn crossover(parent1: &Self, parent2: &Self, sets: &Settings) -> Self {
-        let (male, female) = if parent1.fitness >= parent2.fitness {
+        let order = parent1.compare_fitness(parent2, sets);
+
+        let (male, female) = if order != Ordering::Less {
             (parent1, parent2)
         } else {
             (parent2, parent1)
@@ -272,14 +450,17 @@ impl Genome {
         });
 
         for conn in &male.conns {
-            if f_genes.contains_key(&conn.innov) {
-                let f_gene = *f_genes.get(&conn.innov).unwrap();
+            if let Some(&f_gene) = f_genes.get(&conn.innov) {
                 let mut gene = if rng.gen::<f64>() < 0.5 {
                     f_gene.clone()
                 } else {
                     conn.clone()
                 };
 
+                if sets.crossover_mode == CrossoverMode::Average {
+                    gene.weight = (conn.weight + f_gene.weight) / 2.;
+                }
+
                 let m_e = conn.enabled;
                 let f_e = f_gene.enabled;
 
@@ -303,14 +484,58 @@ impl Genome {
             }
         }
 
+        let mut offspring_nodes = male.nodes.clone();
+
+        // On a fitness tie neither parent's topology is preferred, so the genes
+        // and nodes unique to the female are inherited as well
+        if sets.tie_inherit_both && order == Ordering::Equal {
+            let m_genes = male.conns.iter().map(|c| c.innov).collect::<HashSet<u32>>();
+
+            female
+                .conns
+                .iter()
+                .filter(|c| !m_genes.contains(&c.innov))
+                .for_each(|c| offspring_genes.push(c.clone()));
+
+            for node in &female.nodes {
+                if !offspring_nodes.contains(node) {
+                    offspring_nodes.push(node.clone());
+                }
+            }
+
+            offspring_genes.sort_unstable_by_key(|c| c.innov);
+            offspring_nodes.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+        }
+
         let mut offspring = Self::new(male.inputs, male.outputs, true);
         offspring.conns = offspring_genes;
-        offspring.nodes = male.nodes.clone();
+        offspring.nodes = offspring_nodes;
+        offspring.parents = vec![male.id, female.id];
 
         offspring
     }
 }
 
+impl Clone for Genome {
+    fn clone(&self) -> Self {
+        Self {
+            inputs: self.inputs,
+            outputs: self.outputs,
+            nodes: self.nodes.clone(),
+            conns: self.conns.clone(),
+            fitness: self.fitness,
+            adjusted_fitness: self.adjusted_fitness,
+            id: self.id,
+            parents: self.parents.clone(),
+            mutations: self.mutations.clone(),
+            behavior: self.behavior.clone(),
+            novelty: self.novelty,
+            objectives: self.objectives.clone(),
+            born: self.born,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -345,7 +570,7 @@ mod test {
         let mut hist = History::new(3, 2);
 
         gen.conns.remove(0);
-        hist.conn_history.remove(0);
+        hist.conn_history.remove(&(1, 5));
 
         gen.add_conn(&mut hist);
 
@@ -373,16 +598,108 @@ mod test {
                     == 1
         );
     }
-}
 
-impl Clone for Genome {
-    fn clone(&self) -> Self {
-        Self {
-            inputs: self.inputs,
-            outputs: self.outputs,
-            nodes: self.nodes.clone(),
-            conns: self.conns.clone(),
-            fitness: self.fitness,
+    #[test]
+    fn crossover_average() {
+        let sets = Settings::new(3, 2, 10).crossover_mode(CrossoverMode::Average);
+        let mut gen1 = Genome::new(3, 2, false);
+        let mut gen2 = Genome::new(3, 2, false);
+
+        gen1.conns.iter_mut().for_each(|c| c.weight = 1.);
+        gen2.conns.iter_mut().for_each(|c| c.weight = 0.);
+
+        let child = Genome::crossover(&gen1, &gen2, &sets);
+
+        assert!(child.conns.iter().all(|c| c.weight == 0.5));
+    }
+
+    #[test]
+    fn crossover_tie_inherit_both() {
+        let sets = Settings::new(3, 2, 10).tie_inherit_both(true);
+        let mut hist = History::new(3, 2);
+        let gen1 = Genome::new(3, 2, false);
+        let mut gen2 = gen1.clone();
+
+        gen2.add_node(&mut hist);
+        gen2.conns.sort_unstable_by_key(|c| c.innov);
+
+        let child = Genome::crossover(&gen1, &gen2, &sets);
+
+        assert!(child.conns.len() == 10 && child.nodes.len() == 7);
+        assert!(child.feed_forward(&vec![1., 1., 1.]).is_ok());
+    }
+
+    #[test]
+    fn crossover_size_tiebreak() {
+        let sets = Settings::new(3, 2, 10)
+            .tie_inherit_both(true)
+            .size_tiebreak(true);
+        let mut hist = History::new(3, 2);
+        let gen1 = Genome::new(3, 2, false);
+        let mut gen2 = gen1.clone();
+
+        gen2.add_node(&mut hist);
+        gen2.conns.sort_unstable_by_key(|c| c.innov);
+
+        // The smaller parent wins the tie, so only its genes are inherited
+        for (a, b) in &[(&gen1, &gen2), (&gen2, &gen1)] {
+            let child = Genome::crossover(a, b, &sets);
+            assert!(child.conns.len() == 8 && child.nodes.len() == 6);
         }
+
+        // The selection score decides before size does
+        let mut gen2 = gen2.clone();
+        gen2.adjusted_fitness = 1.;
+        let child = Genome::crossover(&gen1, &gen2, &sets);
+        assert!(child.conns.len() == 10 && child.nodes.len() == 7);
+    }
+
+    #[test]
+    fn distance_breakdown() {
+        let sets = Settings::new(3, 2, 10);
+        let mut hist = History::new(3, 2);
+        let gen1 = Genome::new(3, 2, false);
+        let mut gen2 = gen1.clone();
+
+        gen2.conns.remove(0);
+        gen2.add_node(&mut hist);
+        gen2.conns.sort_unstable_by_key(|c| c.innov);
+
+        let cmp = gen1.compare(&gen2);
+
+        assert_eq!((cmp.disjoint, cmp.excess, cmp.matching), (1, 2, 7));
+        assert_eq!(cmp.mean_weight_diff, 0.);
+        assert_eq!(gen1.distance(&gen2, &sets), 3.);
+        assert_eq!(gen1.distance(&gen1, &sets), 0.);
+    }
+
+    #[test]
+    fn mutations_recorded() {
+        let mut hist = History::new(3, 2);
+        let mut gen = Genome::new(3, 2, false);
+        let sets = Settings::new(3, 2, 1)
+            .wt_mut_rate(1.)
+            .conn_mut_rate(1.)
+            .node_mut_rate(1.);
+
+        // Fully connected, so no connection can be added before the node is
+        gen.mutate(&mut hist, &sets);
+        let node = gen.node_innovs().max().unwrap();
+        assert_eq!(
+            gen.mutations,
+            vec![Mutation::Weights(8), Mutation::AddNode(node)]
+        );
+
+        let conn = gen.conns.iter().map(|c| c.innov).max().unwrap();
+        gen.mutate(
+            &mut hist,
+            &Settings::new(3, 2, 1)
+                .wt_mut_rate(0.)
+                .conn_mut_rate(1.)
+                .node_mut_rate(0.),
+        );
+        let added = gen.conns.iter().map(|c| c.innov).max().unwrap();
+        assert!(added > conn);
+        assert_eq!(gen.mutations[2], Mutation::AddConnection(added));
     }
 }
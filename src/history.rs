@@ -1,32 +1,27 @@
 use crate::connection::Connection;
+use crate::genome::Genome;
 use crate::node::Node;
 
-use std::vec::Vec;
+use std::collections::{HashMap, HashSet};
 
 pub struct History {
-    pub conn_history: Vec<HistConnection>,
+    pub conn_history: HashMap<(u32, u32), u32>, // (from, to) -> Innovation Number
+    outgoing: HashMap<u32, Vec<u32>>,           // from -> every to, in order of innovation
+    splits: HashMap<(u32, u32), NodeMut>,       // Split connection (from, to) -> New Node
+    origins: HashMap<u32, (u32, u32)>,          // Node -> Connection (from, to) it split
+    inputs: u32,
+    outputs: u32,
     next_node_innov: u32,
     next_conn_innov: u32,
 }
 
-pub struct HistConnection {
-    innov: u32,
-    from: u32,
-    to: u32,
-}
-
+#[derive(Clone, Copy)]
 pub struct NodeMut {
     pub node: u32,
     pub in_conn: u32,
     pub out_conn: u32,
 }
 
-impl HistConnection {
-    fn new(innov: u32, from: u32, to: u32) -> Self {
-        Self { innov, from, to }
-    }
-}
-
 impl NodeMut {
     fn new(node: u32, in_conn: u32, out_conn: u32) -> Self {
         Self {
@@ -40,80 +35,253 @@ impl NodeMut {
 impl History {
     pub fn new(inputs: u32, outputs: u32) -> Self {
         let mut hist = Self {
-            conn_history: Vec::with_capacity(((inputs + 1) * outputs + 1) as usize),
+            conn_history: HashMap::with_capacity(((inputs + 1) * outputs + 1) as usize),
+            outgoing: HashMap::new(),
+            splits: HashMap::new(),
+            origins: HashMap::new(),
+            inputs,
+            outputs,
             next_node_innov: inputs + outputs + 2,
             next_conn_innov: (inputs + 1) * outputs + 1,
         };
 
+        hist.insert_initial_conns();
+
+        hist
+    }
+
+    // Connections from every input and the bias to every output, numbered as in
+    // Genome::new
+    fn insert_initial_conns(&mut self) {
         let mut innov = 1;
-        for inp in 1..=(inputs + 1) {
-            for out in (inputs + 2)..(inputs + outputs + 2) {
-                hist.conn_history.push(HistConnection::new(innov, inp, out));
+        for inp in 1..=(self.inputs + 1) {
+            for out in (self.inputs + 2)..(self.inputs + self.outputs + 2) {
+                self.insert_conn(inp, out, innov);
                 innov += 1;
             }
         }
+    }
 
-        hist
+    fn insert_conn(&mut self, from: u32, to: u32, innov: u32) {
+        self.conn_history.insert((from, to), innov);
+        self.outgoing.entry(from).or_default().push(to);
     }
 
     pub fn mutate_conn(&mut self, from: &Node, to: &Node) -> u32 {
-        match self
-            .conn_history
-            .iter()
-            .find(|c| c.from == from.innov && c.to == to.innov)
-        {
-            Some(conn) => conn.innov,
+        self.conn_innov(from.innov, to.innov)
+    }
+
+    fn conn_innov(&mut self, from: u32, to: u32) -> u32 {
+        match self.conn_history.get(&(from, to)) {
+            Some(&innov) => innov,
             None => {
                 let res = self.next_conn_innov;
                 self.next_conn_innov += 1;
-                self.conn_history
-                    .push(HistConnection::new(res, from.innov, to.innov));
+                self.insert_conn(from, to, res);
                 res
             }
         }
     }
 
+    // Finds a node already sitting between the ends of the connection
+    fn find_bridge(&self, conn: &Connection) -> Option<NodeMut> {
+        self.outgoing.get(&conn.from)?.iter().find_map(|&node| {
+            let in_conn = self.conn_history.get(&(conn.from, node))?;
+            let out_conn = self.conn_history.get(&(node, conn.to))?;
+            Some(NodeMut::new(node, *in_conn, *out_conn))
+        })
+    }
+
     pub fn mutate_node(&mut self, conn: &Connection) -> NodeMut {
-        let from_conns = self
-            .conn_history
-            .iter()
-            .filter(|c| c.from == conn.from)
-            .collect::<Vec<&HistConnection>>();
-        let to_conns = self
-            .conn_history
-            .iter()
-            .filter(|c| c.to == conn.to)
-            .collect::<Vec<&HistConnection>>();
-
-        let mut res: Option<NodeMut> = None;
-
-        'outer: for from_conn in from_conns {
-            for to_conn in &to_conns {
-                if from_conn.to == to_conn.from {
-                    res = Some(NodeMut::new(from_conn.to, from_conn.innov, to_conn.innov));
-                    break 'outer;
-                }
-            }
+        if let Some(details) = self.splits.get(&(conn.from, conn.to)) {
+            return *details;
+        }
+
+        if let Some(details) = self.find_bridge(conn) {
+            self.splits.insert((conn.from, conn.to), details);
+            return details;
+        }
+
+        let new_node_innov = self.next_node_innov;
+        self.next_node_innov += 1;
+        let new_in_innov = self.next_conn_innov;
+        self.next_conn_innov += 1;
+        let new_out_innov = self.next_conn_innov;
+        self.next_conn_innov += 1;
+
+        self.insert_conn(conn.from, new_node_innov, new_in_innov);
+        self.insert_conn(new_node_innov, conn.to, new_out_innov);
+
+        let details = NodeMut::new(new_node_innov, new_in_innov, new_out_innov);
+        self.splits.insert((conn.from, conn.to), details);
+        self.origins.insert(new_node_innov, (conn.from, conn.to));
+
+        details
+    }
+
+    // Forgets every innovation, so structural mutations are only matched within a
+    // generation as in the NEAT paper. Numbers keep counting up, so new innovations
+    // never collide with ones still present in the population. Node origins are
+    // kept, they are only needed to import genomes from another History. So are the
+    // initial connections, which every genome shares from the start.
+    pub fn new_generation(&mut self) {
+        self.conn_history.clear();
+        self.outgoing.clear();
+        self.splits.clear();
+        self.insert_initial_conns();
+    }
+
+    // Forgets innovations that no longer appear in any of the given genomes
+    pub fn prune<'a>(&mut self, genomes: impl Iterator<Item = &'a Genome>) {
+        let mut conns = HashSet::<u32>::new();
+        let mut nodes = HashSet::<u32>::new();
+
+        for genome in genomes {
+            conns.extend(genome.conns.iter().map(|c| c.innov));
+            nodes.extend(genome.node_innovs());
+        }
+
+        self.conn_history.retain(|_, innov| conns.contains(innov));
+
+        let conn_history = &self.conn_history;
+        self.outgoing.retain(|from, tos| {
+            tos.retain(|to| conn_history.contains_key(&(*from, *to)));
+            !tos.is_empty()
+        });
+
+        self.splits
+            .retain(|_, details| nodes.contains(&details.node));
+        self.origins.retain(|node, _| nodes.contains(node));
+    }
+
+    // Innovation number in this History of a node from the source History. Hidden
+    // nodes are matched by the connection they split, whose ends are imported first.
+    fn import_node(&mut self, node: u32, source: &History, map: &mut HashMap<u32, u32>) -> u32 {
+        if node <= self.inputs + self.outputs + 1 {
+            return node;
+        }
+
+        if let Some(&innov) = map.get(&node) {
+            return innov;
         }
 
-        match res {
-            Some(innov) => innov,
+        let innov = match source.origins.get(&node) {
+            Some(&(from, to)) => {
+                let from = self.import_node(from, source, map);
+                let to = self.import_node(to, source, map);
+                self.mutate_node(&Connection::new(0, from, to, 0., true))
+                    .node
+            }
             None => {
-                let new_node_innov = self.next_node_innov;
+                let innov = self.next_node_innov;
                 self.next_node_innov += 1;
-                let new_in_innov = self.next_conn_innov;
-                self.next_conn_innov += 1;
-                let new_in = HistConnection::new(new_in_innov, conn.from, new_node_innov);
+                innov
+            }
+        };
 
-                let new_out_innov = self.next_conn_innov;
-                self.next_conn_innov += 1;
-                let new_out = HistConnection::new(new_out_innov, new_node_innov, conn.to);
+        map.insert(node, innov);
+        innov
+    }
 
-                self.conn_history.push(new_in);
-                self.conn_history.push(new_out);
+    // Renumbers the nodes and connections of a Genome bred under the source History
+    // so that they match the innovations of this one, registering structure this
+    // History hasn't seen yet
+    pub fn import(&mut self, genome: &Genome, source: &History) -> Genome {
+        let mut map = HashMap::<u32, u32>::new();
+        let mut res = genome.clone();
 
-                NodeMut::new(new_node_innov, new_in_innov, new_out_innov)
-            }
+        for innov in genome.node_innovs() {
+            self.import_node(innov, source, &mut map);
         }
+
+        let lookup = |innov: u32| *map.get(&innov).unwrap_or(&innov);
+
+        res.renumber(lookup, |from, to| self.conn_innov(from, to));
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn node_mut_matches_split() {
+        let mut hist = History::new(3, 2);
+        let conn = Connection::new(1, 1, 5, 1., true);
+
+        let first = hist.mutate_node(&conn);
+        let second = hist.mutate_node(&conn);
+
+        assert_eq!(
+            (first.node, first.in_conn, first.out_conn),
+            (second.node, second.in_conn, second.out_conn)
+        );
+    }
+
+    #[test]
+    fn new_generation_forgets_matches() {
+        let mut hist = History::new(3, 2);
+        let conn = Connection::new(1, 1, 5, 1., true);
+
+        let first = hist.mutate_node(&conn);
+        hist.new_generation();
+        let second = hist.mutate_node(&conn);
+
+        assert!(second.node > first.node && second.in_conn > first.out_conn);
+    }
+
+    #[test]
+    fn import_matches_splits() {
+        let mut source = History::new(3, 2);
+        let mut hist = History::new(3, 2);
+        let mut genome = Genome::new(3, 2, false);
+        let sets = Settings::new(3, 2, 1).conn_mut_rate(0.).node_mut_rate(1.);
+
+        source.mutate_node(&Connection::new(2, 2, 5, 1., true));
+        genome.mutate(&mut source, &sets);
+
+        let imported = hist.import(&genome, &source);
+        let split = imported.conns.iter().find(|c| !c.enabled).unwrap();
+        let details = hist.mutate_node(split);
+
+        assert!(imported.node_innovs().any(|n| n == details.node));
+        assert!(imported.conns.iter().any(|c| c.innov == details.in_conn));
+        assert!(imported.conns.iter().any(|c| c.innov == details.out_conn));
+    }
+
+    #[test]
+    fn import_after_new_generation() {
+        let mut source = History::new(3, 2);
+        let mut hist = History::new(3, 2);
+        let mut genome = Genome::new(3, 2, false);
+        let sets = Settings::new(3, 2, 1).conn_mut_rate(0.).node_mut_rate(1.);
+
+        source.new_generation();
+        hist.new_generation();
+        genome.mutate(&mut source, &sets);
+
+        let imported = hist.import(&genome, &source);
+
+        // The initial connections keep their numbers, so imports stay comparable
+        assert!((1..=8).all(|innov| imported.conns.iter().any(|c| c.innov == innov)));
+        assert!(imported
+            .distance(&Genome::new(3, 2, false), &sets)
+            .is_finite());
+    }
+
+    #[test]
+    fn prune_unused_innovations() {
+        let mut hist = History::new(3, 2);
+        let genome = Genome::new(3, 2, false);
+        let conn = Connection::new(1, 1, 5, 1., true);
+
+        let split = hist.mutate_node(&conn);
+        hist.prune(std::iter::once(&genome));
+
+        assert_eq!(hist.conn_history.len(), 8);
+        assert!(hist.mutate_node(&conn).node > split.node);
     }
 }
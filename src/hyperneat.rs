@@ -0,0 +1,203 @@
+use crate::genome::Genome;
+use crate::network::Network;
+
+use std::vec::Vec;
+
+// Rule deciding whether a connection queried from the CPPN is expressed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Expression {
+    // Expressed when the magnitude of the weight output exceeds the weight threshold
+    Threshold,
+    // Expressed when the second CPPN output (Link Expression Output) exceeds 0.5
+    Leo,
+}
+
+// Geometric layout of the phenotype network. Every node is given a coordinate and
+// the CPPN is queried with the coordinates of both ends of each potential connection.
+// Connections lead from the inputs through every hidden layer in turn to the outputs.
+pub struct Substrate {
+    pub inputs: Vec<Vec<f64>>,
+    pub hidden: Vec<Vec<Vec<f64>>>, // Hidden layers, from the inputs towards the outputs
+    pub outputs: Vec<Vec<f64>>,
+    pub weight_threshold: f64,
+    pub max_weight: f64,
+    pub expression: Expression,
+}
+
+impl Substrate {
+    pub fn new(inputs: Vec<Vec<f64>>, outputs: Vec<Vec<f64>>) -> Self {
+        Self {
+            inputs,
+            hidden: Vec::new(),
+            outputs,
+            weight_threshold: 0.2,
+            max_weight: 3.,
+            expression: Expression::Threshold,
+        }
+    }
+
+    pub fn hidden_layer(mut self, layer: Vec<Vec<f64>>) -> Self {
+        self.hidden.push(layer);
+        self
+    }
+
+    pub fn weight_threshold(mut self, threshold: f64) -> Self {
+        self.weight_threshold = threshold;
+        self
+    }
+
+    pub fn max_weight(mut self, weight: f64) -> Self {
+        self.max_weight = weight;
+        self
+    }
+
+    pub fn expression(mut self, expression: Expression) -> Self {
+        self.expression = expression;
+        self
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.inputs.first().map_or(0, |c| c.len())
+    }
+
+    // Input size the CPPN Genomes must be created with
+    pub fn cppn_inputs(&self) -> u32 {
+        2 * self.dimensions() as u32
+    }
+
+    // Output size the CPPN Genomes must be created with
+    pub fn cppn_outputs(&self) -> u32 {
+        match self.expression {
+            Expression::Threshold => 1,
+            Expression::Leo => 2,
+        }
+    }
+
+    fn query_cppn(cppn: &Genome, from: &[f64], to: &[f64]) -> Result<Vec<f64>, &'static str> {
+        let input = from.iter().chain(to).copied().collect::<Vec<f64>>();
+        cppn.feed_forward(&input)
+    }
+
+    // Raw weight output of the CPPN between two coordinates, in (-1, 1)
+    pub fn cppn_weight(cppn: &Genome, from: &[f64], to: &[f64]) -> Result<f64, &'static str> {
+        // The CPPN outputs lie in (0, 1)
+        Ok(Self::query_cppn(cppn, from, to)?[0] * 2. - 1.)
+    }
+
+    // Weight of the connection between two coordinates, None when not expressed
+    pub fn query(
+        &self,
+        cppn: &Genome,
+        from: &[f64],
+        to: &[f64],
+    ) -> Result<Option<f64>, &'static str> {
+        let output = Self::query_cppn(cppn, from, to)?;
+
+        // The CPPN outputs lie in (0, 1)
+        let weight = output[0] * 2. - 1.;
+
+        let weight = match self.expression {
+            Expression::Threshold => {
+                if weight.abs() <= self.weight_threshold {
+                    return Ok(None);
+                }
+
+                weight.signum() * (weight.abs() - self.weight_threshold)
+                    / (1. - self.weight_threshold)
+            }
+            Expression::Leo => {
+                if output.get(1).is_none_or(|&leo| leo <= 0.5) {
+                    return Ok(None);
+                }
+
+                weight
+            }
+        };
+
+        Ok(Some(weight * self.max_weight))
+    }
+
+    pub fn build(&self, cppn: &Genome) -> Result<Network, &'static str> {
+        let dims = self.dimensions();
+
+        let all_coords = self
+            .inputs
+            .iter()
+            .chain(self.hidden.iter().flatten())
+            .chain(&self.outputs);
+
+        for coord in all_coords {
+            if coord.len() != dims {
+                return Err("Substrate coordinates must all have the same dimensions");
+            }
+        }
+
+        let mut layers = Vec::<&Vec<Vec<f64>>>::with_capacity(self.hidden.len() + 2);
+        layers.push(&self.inputs);
+        layers.extend(&self.hidden);
+        layers.push(&self.outputs);
+
+        let nodes = layers.iter().map(|l| l.len()).sum::<usize>();
+        let outputs = (nodes - self.outputs.len()..nodes).collect();
+
+        let mut network = Network::new(self.inputs.len(), nodes, outputs);
+
+        let mut offset = 0;
+
+        for pair in layers.windows(2) {
+            let (from_layer, to_layer) = (pair[0], pair[1]);
+            let to_offset = offset + from_layer.len();
+
+            for (i, from) in from_layer.iter().enumerate() {
+                for (o, to) in to_layer.iter().enumerate() {
+                    if let Some(weight) = self.query(cppn, from, to)? {
+                        network.add_conn(offset + i, to_offset + o, weight)?;
+                    }
+                }
+            }
+
+            offset = to_offset;
+        }
+
+        Ok(network)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn substrate() -> Substrate {
+        Substrate::new(
+            vec![vec![-1., -1.], vec![0., -1.], vec![1., -1.]],
+            vec![vec![-1., 1.], vec![1., 1.]],
+        )
+        .hidden_layer(vec![vec![-0.5, 0.], vec![0.5, 0.]])
+    }
+
+    #[test]
+    fn build_layered_network() {
+        let substrate = substrate().weight_threshold(0.);
+        let cppn = Genome::new(substrate.cppn_inputs(), substrate.cppn_outputs(), false);
+
+        let network = substrate.build(&cppn).unwrap();
+
+        assert_eq!(network.nodes(), 7);
+        assert!(network.conns() <= 10);
+        assert_eq!(network.feed_forward(&vec![1., 0., 1.]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn threshold_prunes_weak_links() {
+        let substrate = substrate().weight_threshold(1.);
+        let cppn = Genome::new(substrate.cppn_inputs(), substrate.cppn_outputs(), false);
+
+        let network = substrate.build(&cppn).unwrap();
+
+        assert_eq!(network.conns(), 0);
+        assert_eq!(
+            network.feed_forward(&vec![1., 0., 1.]).unwrap(),
+            vec![0.5, 0.5]
+        );
+    }
+}
@@ -1,10 +1,34 @@
+mod archipelago;
+mod coevolution;
 mod connection;
+mod cooperation;
+mod es_hyperneat;
 mod genome;
 mod history;
+mod hyperneat;
+mod lineage;
+mod network;
 mod node;
+mod novelty;
+mod nsga;
 mod population;
 mod settings;
+mod speciation;
 mod species;
+mod stats;
 
+pub use archipelago::{Archipelago, Topology};
+pub use coevolution::{Coevolution, Matchup};
+pub use cooperation::{Cooperation, Credit};
+pub use es_hyperneat::{EsNetwork, EsSubstrate};
+pub use genome::{GeneComparison, Genome};
+pub use hyperneat::{Expression, Substrate};
+pub use lineage::{Birth, Mutation};
+pub use network::Network;
 pub use population::Population;
-pub use settings::Settings;
+pub use settings::{
+    CrossoverMode, FitnessSharing, GeneNormalization, InnovationMatching, RepresentativeStrategy,
+    SelectionStrategy, Settings, StagnationAction,
+};
+pub use species::Species;
+pub use stats::Stats;
@@ -0,0 +1,19 @@
+use std::vec::Vec;
+
+// Mutation applied to a Genome right after it was bred
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mutation {
+    Weights(usize),     // Number of connection weights changed
+    AddConnection(u32), // Innovation number of the new connection
+    AddNode(u32),       // Innovation number of the new node
+}
+
+// Record of a single Genome being born into the Population
+#[derive(Clone, Debug)]
+pub struct Birth {
+    pub generation: u64, // Generation the child was born into
+    pub child: u64,      // ID of the child Genome
+    pub parents: Vec<u64>,
+    pub mutations: Vec<Mutation>,
+    pub species: Option<u64>, // ID of the Species the parents were bred in
+}
@@ -0,0 +1,59 @@
+use crate::node::Node;
+
+use std::vec::Vec;
+
+// Fixed phenotype network built from a substrate. Nodes are stored inputs first,
+// and every connection leads from a lower to a higher node index, so a single
+// pass over the nodes in order is enough to propagate the inputs.
+pub struct Network {
+    inputs: usize,
+    outputs: Vec<usize>,              // Indices of the output nodes
+    incoming: Vec<Vec<(usize, f64)>>, // (from, weight) of every connection into each node
+}
+
+impl Network {
+    pub fn new(inputs: usize, nodes: usize, outputs: Vec<usize>) -> Self {
+        Self {
+            inputs,
+            outputs,
+            incoming: vec![Vec::new(); nodes],
+        }
+    }
+
+    pub fn add_conn(&mut self, from: usize, to: usize, weight: f64) -> Result<(), &'static str> {
+        if from >= to || to >= self.incoming.len() {
+            return Err("Connections must lead to a later node of the network");
+        }
+
+        self.incoming[to].push((from, weight));
+
+        Ok(())
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.incoming.len()
+    }
+
+    pub fn conns(&self) -> usize {
+        self.incoming.iter().map(|i| i.len()).sum()
+    }
+
+    pub fn feed_forward(&self, input: &Vec<f64>) -> Result<Vec<f64>, &'static str> {
+        if input.len() != self.inputs {
+            return Err("Provided input size doesn't match Network input size");
+        }
+
+        let mut node_vals = Vec::<f64>::with_capacity(self.incoming.len());
+        node_vals.extend(input);
+
+        for incoming in &self.incoming[self.inputs..] {
+            let sum = incoming
+                .iter()
+                .fold(0., |acc, &(from, weight)| acc + node_vals[from] * weight);
+
+            node_vals.push(Node::activate(sum, 1.));
+        }
+
+        Ok(self.outputs.iter().map(|&o| node_vals[o]).collect())
+    }
+}
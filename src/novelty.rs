@@ -0,0 +1,37 @@
+use std::vec::Vec;
+
+fn behavior_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .fold(0., |acc, (x, y)| acc + (x - y).powi(2))
+        .sqrt()
+}
+
+// Mean distance from the behavior to its k nearest neighbours among the others
+pub fn novelty<'a>(behavior: &[f64], others: impl Iterator<Item = &'a Vec<f64>>, k: usize) -> f64 {
+    let mut distances = others
+        .map(|o| behavior_distance(behavior, o))
+        .collect::<Vec<f64>>();
+
+    if distances.is_empty() || k == 0 {
+        return 0.;
+    }
+
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    distances.truncate(k);
+
+    distances.iter().sum::<f64>() / distances.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn novelty_of_nearest() {
+        let others = [vec![0., 1.], vec![0., 3.], vec![10., 10.]];
+
+        assert_eq!(novelty(&[0., 0.], others.iter(), 2), 2.);
+        assert_eq!(novelty(&[0., 0.], [].iter(), 2), 0.);
+    }
+}
@@ -0,0 +1,105 @@
+use std::vec::Vec;
+
+// Every objective is maximized
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(x, y)| x >= y) && a.iter().zip(b).any(|(x, y)| x > y)
+}
+
+// Splits the indices of the objective vectors into Pareto fronts, best first
+pub fn non_dominated_sort(objectives: &[&Vec<f64>]) -> Vec<Vec<usize>> {
+    let len = objectives.len();
+
+    let mut dominated_by = vec![0; len];
+    let mut dominates_list = vec![Vec::<usize>::new(); len];
+
+    for i in 0..len {
+        for j in 0..len {
+            if dominates(objectives[i], objectives[j]) {
+                dominates_list[i].push(j);
+            } else if dominates(objectives[j], objectives[i]) {
+                dominated_by[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = Vec::<Vec<usize>>::new();
+    let mut current = (0..len)
+        .filter(|&i| dominated_by[i] == 0)
+        .collect::<Vec<usize>>();
+
+    while !current.is_empty() {
+        let mut next = Vec::<usize>::new();
+
+        for &i in &current {
+            for &j in &dominates_list[i] {
+                dominated_by[j] -= 1;
+                if dominated_by[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+
+        fronts.push(current);
+        current = next;
+    }
+
+    fronts
+}
+
+// Crowding distance of every member of a front, in the order of the front
+pub fn crowding_distance(objectives: &[&Vec<f64>], front: &[usize]) -> Vec<f64> {
+    let mut distances = vec![0.; front.len()];
+    let count = front
+        .iter()
+        .map(|&i| objectives[i].len())
+        .min()
+        .unwrap_or(0);
+
+    #[allow(clippy::needless_range_loop)]
+    for m in 0..count {
+        let mut order = (0..front.len()).collect::<Vec<usize>>();
+        order.sort_by(|&a, &b| {
+            objectives[front[a]][m]
+                .partial_cmp(&objectives[front[b]][m])
+                .unwrap()
+        });
+
+        let min = objectives[front[order[0]]][m];
+        let max = objectives[front[order[order.len() - 1]]][m];
+
+        distances[order[0]] = f64::INFINITY;
+        distances[order[order.len() - 1]] = f64::INFINITY;
+
+        if max - min <= 0. {
+            continue;
+        }
+
+        for w in order.windows(3) {
+            let (prev, curr, next) = (w[0], w[1], w[2]);
+            distances[curr] +=
+                (objectives[front[next]][m] - objectives[front[prev]][m]) / (max - min);
+        }
+    }
+
+    distances
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sorts_fronts() {
+        let objectives = [vec![1., 1.], vec![2., 0.], vec![0., 2.], vec![0., 0.]];
+        let refs = objectives.iter().collect::<Vec<&Vec<f64>>>();
+
+        let fronts = non_dominated_sort(&refs);
+
+        assert_eq!(fronts.len(), 2);
+        assert_eq!(fronts[1], vec![3]);
+
+        let crowding = crowding_distance(&refs, &[1, 0, 2]);
+        assert!(crowding[0].is_infinite() && crowding[2].is_infinite());
+        assert_eq!(crowding[1], 2.);
+    }
+}
@@ -1,8 +1,17 @@
 use crate::genome::Genome;
 use crate::history::History;
-use crate::settings::Settings;
+use crate::lineage::Birth;
+use crate::novelty;
+use crate::nsga;
+use crate::settings::{InnovationMatching, Settings, StagnationAction};
+use crate::speciation::{self, DistanceCache};
 use crate::species::Species;
+use crate::stats::Stats;
 
+use rand::prelude::*;
+use rand::thread_rng;
+
+use std::collections::{HashMap, HashSet};
 use std::vec::Vec;
 
 pub struct Population {
@@ -13,6 +22,20 @@ pub struct Population {
     pub best_fitness: f64,
     pub best_genome: Option<Genome>,
     pub generations: u64,
+    pub ticks: u64, // Ticks of real time evolution
+    births: u64,    // Offspring bred one at a time by steady state evolution
+    pub stagnancy: u32,
+    pub stats: Stats,
+    pub lineage: Vec<Birth>,
+    pub archive: Vec<Vec<f64>>,    // Behaviors archived by novelty search
+    pub pareto_front: Vec<Genome>, // Non dominated genomes found so far
+    base_mut_rates: Option<(f64, f64, f64)>, // Rates from before a mutation boost
+    asynchronous: bool,            // Whether ask has been called
+    unevaluated: Vec<Genome>,      // Initial genomes not yet asked for
+    pending: HashMap<u64, Genome>, // Genomes asked for, awaiting their fitness
+    distances: DistanceCache,      // Distances between genomes of this generation
+    next_genome_id: u64,
+    next_species_id: u64,
 }
 
 impl Population {
@@ -21,13 +44,27 @@ impl Population {
         let outputs = sets.outputs;
         let pop_size = sets.pop_size as usize;
         let mut pop = Self {
-            sets: sets,
+            sets,
             population: Vec::<Genome>::with_capacity(pop_size),
             species: Vec::new(),
             hist: History::new(inputs, outputs),
             best_fitness: 0.,
             best_genome: None,
             generations: 0,
+            ticks: 0,
+            births: 0,
+            stagnancy: 0,
+            stats: Stats::default(),
+            lineage: Vec::new(),
+            archive: Vec::new(),
+            pareto_front: Vec::new(),
+            base_mut_rates: None,
+            asynchronous: false,
+            unevaluated: Vec::new(),
+            pending: HashMap::new(),
+            distances: DistanceCache::new(),
+            next_genome_id: 0,
+            next_species_id: 0,
         };
 
         pop.reset();
@@ -41,98 +78,1440 @@ impl Population {
         self.best_fitness = 0.;
         self.best_genome = None;
         self.generations = 0;
+        self.ticks = 0;
+        self.births = 0;
+        self.stagnancy = 0;
+        self.stats = Stats::default();
+        self.lineage.clear();
+        self.archive.clear();
+        self.pareto_front.clear();
+        self.asynchronous = false;
+        self.unevaluated.clear();
+        self.pending.clear();
+        self.distances.clear();
+        self.next_genome_id = 0;
+        self.next_species_id = 0;
         self.hist = History::new(self.sets.inputs, self.sets.outputs);
 
         for _ in 0..self.sets.pop_size {
-            let genome = Genome::new(self.sets.inputs, self.sets.outputs, false);
+            let mut genome = Genome::new(self.sets.inputs, self.sets.outputs, false);
+            self.register(&mut genome, None);
+            self.population.push(genome);
+        }
+    }
+
+    // Gives a newborn Genome its ID, recording where it came from if lineage is
+    // being tracked
+    fn register(&mut self, genome: &mut Genome, species: Option<u64>) {
+        genome.id = self.next_genome_id;
+        genome.born = self.ticks;
+        self.next_genome_id += 1;
+
+        if self.sets.record_lineage && !genome.parents.is_empty() {
+            self.lineage.push(Birth {
+                generation: self.generations + 1,
+                child: genome.id,
+                parents: genome.parents.clone(),
+                mutations: genome.mutations.clone(),
+                species,
+            });
+        }
+    }
+
+    // Replaces everything but the champion with fresh genomes. The innovation
+    // history is kept since the champion's genes are still numbered by it.
+    fn reinitialize(&mut self, mut champ: Genome) {
+        self.population.clear();
+        self.species.clear();
+
+        champ.reset_fitness();
+        self.population.push(champ);
+
+        while self.population.len() < self.sets.pop_size as usize {
+            let mut genome = Genome::new(self.sets.inputs, self.sets.outputs, false);
+            self.register(&mut genome, None);
             self.population.push(genome);
         }
     }
 
+    // Sorts by descending selection score, ranking smaller Genomes first on ties
+    // when size_tiebreak is set
+    fn sort_population(&mut self) {
+        let tiebreak = self.sets.size_tiebreak;
+
+        self.population.sort_unstable_by(|a, b| {
+            let order = b.adjusted_fitness.partial_cmp(&a.adjusted_fitness).unwrap();
+
+            if tiebreak {
+                order.then_with(|| a.size().cmp(&b.size()))
+            } else {
+                order
+            }
+        });
+    }
+
+    // Lowers the selection score of every genome by its size. The score may become
+    // negative, while the raw fitness is left as evaluated.
+    fn apply_complexity_penalty(&mut self) {
+        for genome in self.population.iter_mut() {
+            let penalty = self.sets.conn_penalty * genome.enabled_conns() as f64
+                + self.sets.node_penalty * genome.hidden_nodes() as f64;
+
+            genome.adjusted_fitness -= penalty;
+        }
+    }
+
+    // Scores every genome by the novelty of its behavior compared to the rest of the
+    // generation and the archive, and blends it into the selection score
+    fn apply_novelty(&mut self) {
+        let k = self.sets.novelty_k;
+        let weight = self.sets.novelty_weight;
+
+        let novelties = self
+            .population
+            .iter()
+            .enumerate()
+            .map(|(i, g)| {
+                let others = self
+                    .population
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, o)| *j != i && !o.behavior.is_empty())
+                    .map(|(_, o)| &o.behavior)
+                    .chain(&self.archive);
+
+                if g.behavior.is_empty() {
+                    0.
+                } else {
+                    novelty::novelty(&g.behavior, others, k)
+                }
+            })
+            .collect::<Vec<f64>>();
+
+        for (genome, novelty) in self.population.iter_mut().zip(novelties) {
+            genome.novelty = novelty;
+            genome.adjusted_fitness = (1. - weight) * genome.adjusted_fitness + weight * novelty;
+
+            if novelty > self.sets.archive_threshold {
+                self.archive.push(genome.behavior.clone());
+            }
+        }
+
+        if self.archive.len() > self.sets.archive_size {
+            let excess = self.archive.len() - self.sets.archive_size;
+            self.archive.drain(..excess);
+        }
+    }
+
+    // Replaces the selection score of every genome with one that orders them by
+    // Pareto front first and crowding distance second, so the rest of the algorithm
+    // can work with it unchanged. Also refreshes the Pareto front of the run and
+    // returns the IDs of the genomes that newly entered it.
+    fn apply_pareto_ranking(&mut self) -> HashSet<u64> {
+        let objectives = self
+            .population
+            .iter()
+            .map(|g| &g.objectives)
+            .collect::<Vec<&Vec<f64>>>();
+
+        let fronts = nsga::non_dominated_sort(&objectives);
+        let mut scores = vec![0.; objectives.len()];
+
+        for (rank, front) in fronts.iter().enumerate() {
+            let crowding = nsga::crowding_distance(&objectives, front);
+
+            for (&i, c) in front.iter().zip(crowding) {
+                // Crowding is squashed into [0, 0.5] so it never outweighs a front
+                let crowd = if c.is_infinite() {
+                    0.5
+                } else {
+                    c / (1. + c) / 2.
+                };
+                scores[i] = (fronts.len() - rank) as f64 + crowd;
+            }
+        }
+
+        let previous = self
+            .pareto_front
+            .iter()
+            .map(|g| g.id)
+            .collect::<HashSet<u64>>();
+
+        let mut candidates = std::mem::take(&mut self.pareto_front);
+        candidates.extend(
+            self.population
+                .iter()
+                .filter(|g| !g.objectives.is_empty())
+                .cloned(),
+        );
+        candidates.sort_by_key(|g| g.id);
+        candidates.dedup_by_key(|g| g.id);
+
+        let candidate_objectives = candidates
+            .iter()
+            .map(|g| &g.objectives)
+            .collect::<Vec<&Vec<f64>>>();
+        let front = nsga::non_dominated_sort(&candidate_objectives)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+
+        // Genomes with equal objectives never dominate each other, so only the oldest
+        // of them is kept
+        let mut best = Vec::<usize>::with_capacity(front.len());
+        for i in front {
+            if best
+                .iter()
+                .all(|&b| candidates[b].objectives != candidates[i].objectives)
+            {
+                best.push(i);
+            }
+        }
+
+        if best.len() > self.sets.pareto_front_size {
+            let crowding = nsga::crowding_distance(&candidate_objectives, &best);
+            let mut order = (0..best.len()).collect::<Vec<usize>>();
+            order.sort_by(|&a, &b| crowding[b].partial_cmp(&crowding[a]).unwrap());
+            order.truncate(self.sets.pareto_front_size);
+
+            best = order.into_iter().map(|o| best[o]).collect();
+        }
+
+        best.sort_unstable();
+
+        self.pareto_front = best.into_iter().map(|i| candidates[i].clone()).collect();
+
+        for (genome, score) in self.population.iter_mut().zip(scores) {
+            genome.adjusted_fitness = score;
+        }
+
+        self.pareto_front
+            .iter()
+            .map(|g| g.id)
+            .filter(|id| !previous.contains(id))
+            .collect()
+    }
+
+    fn boost_mutation(&mut self, factor: f64) {
+        if self.base_mut_rates.is_some() {
+            return;
+        }
+
+        self.base_mut_rates = Some((
+            self.sets.conn_mut_rate,
+            self.sets.node_mut_rate,
+            self.sets.wt_mut_rate,
+        ));
+
+        self.sets.conn_mut_rate = (self.sets.conn_mut_rate * factor).min(1.);
+        self.sets.node_mut_rate = (self.sets.node_mut_rate * factor).min(1.);
+        self.sets.wt_mut_rate = (self.sets.wt_mut_rate * factor).min(1.);
+    }
+
+    fn restore_mutation(&mut self) {
+        if let Some((conn, node, wt)) = self.base_mut_rates.take() {
+            self.sets.conn_mut_rate = conn;
+            self.sets.node_mut_rate = node;
+            self.sets.wt_mut_rate = wt;
+        }
+    }
+
     pub fn next_generation(&mut self) {
-        self.population
-            .sort_unstable_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+        // Nothing to breed from, as when every citizen was handed out by ask and none
+        // has been told about yet
+        if self.population.is_empty() {
+            return;
+        }
+
+        // Selection works on a score that starts out as the raw fitness, which is kept
+        // as evaluated for tracking the champion and stagnancy
+        for genome in self.population.iter_mut() {
+            genome.adjusted_fitness = genome.fitness;
+        }
+
+        self.sort_population();
 
         let mut this_champ = self.population[0].clone();
-        if this_champ.fitness > self.best_fitness {
+        if this_champ.fitness > self.best_fitness || self.best_genome.is_none() {
             self.best_fitness = this_champ.fitness;
             self.best_genome = Some(this_champ.clone());
+            self.stagnancy = 0;
+            self.restore_mutation();
+        } else {
+            self.stagnancy += 1;
+        }
+
+        this_champ.reset_fitness();
+
+        let penalized = self.sets.conn_penalty > 0. || self.sets.node_penalty > 0.;
+
+        if penalized {
+            self.apply_complexity_penalty();
+        }
+
+        if self.sets.novelty_weight > 0. {
+            self.apply_novelty();
+        }
+
+        // Raw fitness may not be evaluated at all when selecting on objectives, so
+        // widening the Pareto front also counts as progress
+        let pareto_newcomers = if self.sets.multi_objective {
+            let newcomers = self.apply_pareto_ranking();
+
+            if !newcomers.is_empty() {
+                self.stagnancy = 0;
+                self.restore_mutation();
+            }
+
+            newcomers
+        } else {
+            HashSet::new()
+        };
+
+        if penalized || self.sets.novelty_weight > 0. || self.sets.multi_objective {
+            self.sort_population();
         }
 
-        this_champ.fitness = 0.;
+        let stagnant = match self.sets.population_stagnancy {
+            Some(allowed) => self.stagnancy >= allowed,
+            None => false,
+        };
+
+        self.stats.stagnancy = self.stagnancy;
+
+        let mut delta_coding = false;
+
+        if stagnant {
+            self.stagnancy = 0;
+
+            match self.sets.stagnation_action {
+                StagnationAction::DeltaCoding => delta_coding = true,
+                StagnationAction::Reinitialize => {
+                    let champ = self.best_genome.clone().unwrap_or(this_champ);
+                    self.reinitialize(champ);
+                    self.generations += 1;
+                    self.stats.generation = self.generations;
+                    self.stats.best_fitness = self.best_fitness;
+                    return;
+                }
+                StagnationAction::BoostMutation(factor) => self.boost_mutation(factor),
+            }
+        }
 
         self.speciate_population();
 
+        self.stats.species = self.species.len();
+        self.stats.speciation_threshold = self.sets.speciation_threshold;
+
+        self.adjust_threshold();
+
+        let sets = &self.sets;
+        let distances = &mut self.distances;
+        let generation = self.generations;
+        let (min_fitness, max_fitness) = self
+            .species
+            .iter()
+            .flat_map(|s| s.genomes.iter())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), g| {
+                (min.min(g.adjusted_fitness), max.max(g.adjusted_fitness))
+            });
+
         self.species.iter_mut().for_each(|s| {
+            s.update_representative(sets, distances);
+            s.update_age(generation);
             s.update_stagnancy();
-            s.fitness_sharing();
-            s.cull_lower_half();
+            if s.genomes.iter().any(|g| pareto_newcomers.contains(&g.id)) {
+                s.stagnancy = 0;
+            }
+            s.fitness_sharing(sets, min_fitness, max_fitness);
+            s.cull(sets);
         });
 
-        let allowed_stagnancy = self.sets.allowed_stagnancy;
-
-        self.species.retain(|s| s.stagnancy < allowed_stagnancy);
+        if delta_coding {
+            self.species.sort_by(|a, b| {
+                let fa = a.genomes.first().map_or(f64::NEG_INFINITY, |g| g.fitness);
+                let fb = b.genomes.first().map_or(f64::NEG_INFINITY, |g| g.fitness);
+                fb.partial_cmp(&fa).unwrap()
+            });
+            self.species.truncate(2);
+            self.species.iter_mut().for_each(|s| s.stagnancy = 0);
+        }
 
-        let total_avg_fitness = self.species.iter().fold(0., |acc, s| acc + s.avg_fitness);
+        self.remove_stagnant_species();
 
-        let pop_size = self.sets.pop_size;
+        // The global champion takes up one slot of the next generation
+        let pop_size = if self.sets.global_elitism {
+            self.sets.pop_size.saturating_sub(1)
+        } else {
+            self.sets.pop_size
+        };
 
-        self.species.iter_mut().for_each(|s| {
-            s.assigned_offspring = (s.avg_fitness / total_avg_fitness * pop_size as f64) as usize;
-        });
+        self.allocate_offspring(pop_size as usize);
 
         self.species.retain(|s| s.assigned_offspring > 0);
 
-        let mut progeny = Vec::<Genome>::with_capacity(pop_size as usize);
+        if self.sets.innovation_matching == InnovationMatching::PerGeneration {
+            self.hist.new_generation();
+        }
+
+        let mut progeny = Vec::<Genome>::with_capacity(self.sets.pop_size as usize);
+
+        // The best genome found so far, which may no longer be in the population
+        let global_elite = if self.sets.global_elitism {
+            let mut elite = self
+                .best_genome
+                .clone()
+                .unwrap_or_else(|| this_champ.clone());
+            elite.reset_fitness();
+            progeny.push(elite.clone());
+            Some(elite.id)
+        } else {
+            None
+        };
+
+        let mut children = Vec::<(Genome, u64)>::new();
 
         for species in &self.species {
             let mut new_offspring = species.assigned_offspring;
 
-            if species.genomes.len() > 3 {
-                let mut champ = species.genomes[0].clone();
-                champ.fitness = 0.;
-                progeny.push(champ);
-                new_offspring -= 1;
+            if species.genomes.len() >= self.sets.elitism_min_size {
+                let elites = self.sets.species_elites.min(new_offspring);
+
+                // The global elite is already carried over, so it is not copied twice
+                for elite in species
+                    .genomes
+                    .iter()
+                    .take(elites)
+                    .filter(|g| Some(g.id) != global_elite)
+                {
+                    let mut champ = elite.clone();
+                    champ.reset_fitness();
+                    progeny.push(champ);
+                    new_offspring -= 1;
+                }
             }
 
             for mut child in species.produce_offspring(new_offspring, &self.sets) {
                 child.mutate(&mut self.hist, &self.sets);
-                child.fitness = 0.;
-                progeny.push(child);
+                child.reset_fitness();
+                children.push((child, species.id));
             }
         }
 
-        if progeny.len() < pop_size as usize {
-            while progeny.len() < pop_size as usize {
+        for (mut child, species) in children {
+            self.register(&mut child, Some(species));
+            progeny.push(child);
+        }
+
+        if progeny.len() < self.sets.pop_size as usize {
+            while progeny.len() < self.sets.pop_size as usize {
                 let mut another_child = this_champ.clone();
+                another_child.parents = vec![this_champ.id];
+                another_child.mutations.clear();
                 another_child.mutate(&mut self.hist, &self.sets);
+                self.register(&mut another_child, None);
                 progeny.push(another_child);
             }
         }
 
+        if self.sets.prune_innovations {
+            self.hist
+                .prune(progeny.iter().chain(self.best_genome.as_ref()));
+        }
+
         self.population = progeny;
         self.generations += 1;
+
+        self.stats.generation = self.generations;
+        self.stats.best_fitness = self.best_fitness;
+    }
+
+    // Advances real time evolution (rtNEAT) by one tick. Every replacement_interval
+    // ticks the worst genome old enough to have been evaluated is replaced with a
+    // single offspring, whose ID is returned, and the best genome is updated.
+    // Citizens are evaluated continuously in between and next_generation is never
+    // called.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn tick(&mut self) -> Option<u64> {
+        self.ticks += 1;
+
+        let interval = self.sets.replacement_interval.max(1);
+
+        if self.ticks % interval == 0 {
+            self.replace_worst()
+        } else {
+            None
+        }
+    }
+
+    // Steady state evolution has no generations, so one is counted every pop_size
+    // births for the per generation bookkeeping of species, innovation matching and
+    // pruning
+    #[allow(clippy::manual_is_multiple_of)]
+    fn count_birth(&mut self) {
+        self.births += 1;
+
+        if self.births % self.sets.pop_size.max(1) as u64 != 0 {
+            return;
+        }
+
+        self.update_species();
+        self.distances.clear();
+
+        let sets = &self.sets;
+        let distances = &mut self.distances;
+        let generation = self.generations;
+
+        self.species.iter_mut().for_each(|s| {
+            s.update_representative(sets, distances);
+            s.update_age(generation);
+            s.update_stagnancy();
+        });
+
+        self.generations += 1;
+
+        self.stats.generation = self.generations;
+        self.stats.species = self.species.len();
+        self.stats.speciation_threshold = self.sets.speciation_threshold;
+        self.stats.best_fitness = self.best_fitness;
+
+        if self.sets.innovation_matching == InnovationMatching::PerGeneration {
+            self.hist.new_generation();
+        }
+
+        // Genomes handed out by ask are still alive, even if not evaluated yet
+        if self.sets.prune_innovations {
+            self.hist.prune(
+                self.population
+                    .iter()
+                    .chain(&self.unevaluated)
+                    .chain(self.pending.values())
+                    .chain(self.best_genome.as_ref()),
+            );
+        }
+    }
+
+    // Index of the first species the genome is compatible with
+    fn compatible_species(&self, genome: &Genome) -> Option<usize> {
+        let threshold = self.sets.speciation_threshold;
+
+        self.species
+            .iter()
+            .position(|s| s.representative().distance(genome, &self.sets) < threshold)
+    }
+
+    // Adds a genome to the first species it is compatible with, founding a new one
+    // if there is none
+    fn assign_species(&mut self, genome: Genome) {
+        match self.compatible_species(&genome) {
+            Some(s) => self.species[s].add_genome(genome),
+            None => {
+                self.species
+                    .push(Species::new(genome, self.next_species_id, self.generations));
+                self.next_species_id += 1;
+            }
+        }
+    }
+
+    // Copies the current fitness of the citizens into their species and shares it.
+    // Membership only changes one genome at a time as genomes are born and replaced,
+    // so only citizens that aren't in any species yet, like the initial ones, are
+    // speciated here.
+    fn update_species(&mut self) {
+        let fitness = self
+            .population
+            .iter()
+            .map(|g| (g.id, g.fitness))
+            .collect::<HashMap<u64, f64>>();
+
+        let mut assigned = HashSet::<u64>::new();
+
+        for species in self.species.iter_mut() {
+            species.genomes.retain(|g| fitness.contains_key(&g.id));
+
+            for genome in species.genomes.iter_mut() {
+                genome.fitness = fitness[&genome.id];
+                assigned.insert(genome.id);
+            }
+        }
+
+        self.species.retain(|s| !s.genomes.is_empty());
+
+        let unassigned = self
+            .population
+            .iter()
+            .filter(|g| !assigned.contains(&g.id))
+            .cloned()
+            .collect::<Vec<Genome>>();
+
+        for genome in unassigned {
+            self.assign_species(genome);
+        }
+
+        let (min_fitness, max_fitness) = self
+            .population
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), g| {
+                (min.min(g.fitness), max.max(g.fitness))
+            });
+
+        // Steady state evolution selects on the raw fitness
+        for species in self.species.iter_mut() {
+            species
+                .genomes
+                .iter_mut()
+                .for_each(|g| g.adjusted_fitness = g.fitness);
+            species
+                .genomes
+                .sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+            species.fitness_sharing(&self.sets, min_fitness, max_fitness);
+        }
+    }
+
+    // Member with the lowest shared fitness that is at least min_age ticks old, so
+    // large species give up members first. Species must be up to date.
+    fn worst_genome(&self, min_age: u64) -> Option<u64> {
+        let ticks = self.ticks;
+
+        self.species
+            .iter()
+            .flat_map(|s| s.genomes.iter())
+            .filter(|g| ticks - g.born >= min_age)
+            .min_by(|a, b| a.adjusted_fitness.partial_cmp(&b.adjusted_fitness).unwrap())
+            .map(|g| g.id)
+    }
+
+    // Removes a genome from its species, dropping the species if it was the last
+    // member
+    fn remove_from_species(&mut self, id: u64) {
+        for species in self.species.iter_mut() {
+            species.genomes.retain(|g| g.id != id);
+        }
+
+        self.species.retain(|s| !s.genomes.is_empty());
+    }
+
+    // Breeds a single offspring from a species picked in proportion to its average
+    // shared fitness. Species must be up to date.
+    fn breed_one(&mut self) -> Option<Genome> {
+        let weights = self
+            .species
+            .iter()
+            .map(|s| {
+                if s.avg_fitness.is_finite() && s.avg_fitness > 0. {
+                    s.avg_fitness
+                } else {
+                    0.
+                }
+            })
+            .collect::<Vec<f64>>();
+
+        if weights.is_empty() {
+            return None;
+        }
+
+        let mut rng = thread_rng();
+        let total = weights.iter().sum::<f64>();
+
+        let parent = if total > 0. {
+            let threshold = rng.gen::<f64>() * total;
+            let mut current = 0.;
+
+            weights
+                .iter()
+                .position(|w| {
+                    current += w;
+                    current > threshold
+                })
+                .unwrap_or(0)
+        } else {
+            rng.gen_range(0, weights.len())
+        };
+
+        let mut child = self.species[parent]
+            .produce_offspring(1, &self.sets)
+            .pop()?;
+        child.mutate(&mut self.hist, &self.sets);
+        child.reset_fitness();
+
+        let species = self.species[parent].id;
+        self.register(&mut child, Some(species));
+
+        Some(child)
+    }
+
+    fn replace_worst(&mut self) -> Option<u64> {
+        self.update_species();
+
+        if let Some(champ) = self
+            .population
+            .iter()
+            .max_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap())
+        {
+            if champ.fitness > self.best_fitness {
+                self.best_fitness = champ.fitness;
+                self.best_genome = Some(champ.clone());
+                self.stats.best_fitness = champ.fitness;
+            }
+        }
+
+        let worst = self.worst_genome(self.sets.min_replacement_age)?;
+        let slot = self.population.iter().position(|g| g.id == worst)?;
+
+        self.remove_from_species(worst);
+
+        let child = self.breed_one()?;
+        let id = child.id;
+
+        self.assign_species(child.clone());
+        self.population[slot] = child;
+        self.count_birth();
+
+        self.adjust_threshold();
+
+        Some(id)
+    }
+
+    // Hands out a genome to evaluate for asynchronous evolution, to be returned with
+    // tell once its fitness is known. The initial citizens are handed out first, after
+    // which every call breeds a new offspring from the genomes evaluated so far. The
+    // citizens are then only the evaluated genomes, and next_generation is never
+    // called. A generation passes every pop_size offspring, as in real time
+    // evolution.
+    pub fn ask(&mut self) -> (u64, Genome) {
+        if !self.asynchronous {
+            self.asynchronous = true;
+            self.unevaluated = std::mem::take(&mut self.population);
+            self.unevaluated.reverse();
+        }
+
+        let (genome, bred) = match self.unevaluated.pop() {
+            Some(genome) => (genome, false),
+            None => {
+                self.update_species();
+
+                // More genomes can be asked for than have been evaluated yet
+                match self.breed_one() {
+                    Some(child) => (child, true),
+                    None => {
+                        let mut genome = Genome::new(self.sets.inputs, self.sets.outputs, false);
+                        self.register(&mut genome, None);
+                        (genome, false)
+                    }
+                }
+            }
+        };
+
+        self.pending.insert(genome.id, genome.clone());
+
+        if bred {
+            self.count_birth();
+        }
+
+        (genome.id, genome)
+    }
+
+    // Adds a genome handed out by ask to the evaluated citizens. Once there are more
+    // than pop_size of them, the one with the lowest shared fitness is removed.
+    pub fn tell(&mut self, id: u64, fitness: f64) -> Result<(), &'static str> {
+        let mut genome = match self.pending.remove(&id) {
+            Some(genome) => genome,
+            None => return Err("No Genome with this ID is awaiting its fitness"),
+        };
+
+        genome.add_fitness(fitness);
+
+        if genome.fitness > self.best_fitness {
+            self.best_fitness = genome.fitness;
+            self.best_genome = Some(genome.clone());
+            self.stats.best_fitness = genome.fitness;
+        }
+
+        self.population.push(genome);
+
+        if self.population.len() > self.sets.pop_size as usize {
+            self.update_species();
+
+            if let Some(worst) = self.worst_genome(0) {
+                self.population.retain(|g| g.id != worst);
+                self.remove_from_species(worst);
+            }
+
+            self.adjust_threshold();
+        }
+
+        Ok(())
     }
 
     pub fn get_citizens(&mut self) -> &mut Vec<Genome> {
         &mut self.population
     }
 
+    // (inputs, outputs) of the Genomes
+    pub fn io(&self) -> (u32, u32) {
+        (self.sets.inputs, self.sets.outputs)
+    }
+
+    pub fn get_species(&self) -> &Vec<Species> {
+        &self.species
+    }
+
+    // Copies of the count fittest citizens
+    pub fn emigrants(&self, count: usize) -> Vec<Genome> {
+        let mut ranked = self.population.iter().collect::<Vec<&Genome>>();
+        ranked.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+
+        ranked.into_iter().take(count).cloned().collect()
+    }
+
+    // Replaces the least fit citizens with Genomes from the source Population. Their
+    // genes are renumbered to match this History and they keep their fitness.
+    pub fn immigrate(&mut self, migrants: &[Genome], source: &Population) {
+        self.population
+            .sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+
+        let count = migrants.len().min(self.population.len());
+        self.population.truncate(self.population.len() - count);
+
+        for migrant in migrants.iter().take(count) {
+            let mut genome = self.hist.import(migrant, &source.hist);
+            genome.parents.clear();
+            genome.mutations.clear();
+            self.register(&mut genome, None);
+            self.population.push(genome);
+        }
+    }
+
+    // Removes species that have been stagnant for too long, sparing the
+    // species_elitism best ones and never leaving the population without a species
+    fn remove_stagnant_species(&mut self) {
+        let allowed_stagnancy = self.sets.allowed_stagnancy;
+
+        let mut ranked = (0..self.species.len())
+            .filter(|&i| !self.species[i].genomes.is_empty())
+            .collect::<Vec<usize>>();
+        ranked.sort_by(|&a, &b| {
+            self.species[b]
+                .max_fitness
+                .partial_cmp(&self.species[a].max_fitness)
+                .unwrap()
+        });
+
+        let mut protected = vec![false; self.species.len()];
+        ranked
+            .iter()
+            .take(self.sets.species_elitism)
+            .for_each(|&i| protected[i] = true);
+
+        let survivors = self
+            .species
+            .iter()
+            .zip(&protected)
+            .filter(|(s, &p)| p || s.stagnancy < allowed_stagnancy)
+            .count();
+
+        if survivors == 0 {
+            if let Some(&best) = ranked.first() {
+                protected[best] = true;
+            }
+        }
+
+        let mut protected = protected.into_iter();
+        self.species
+            .retain(|s| protected.next().unwrap() || s.stagnancy < allowed_stagnancy);
+    }
+
+    fn allocate_offspring(&mut self, pop_size: usize) {
+        // Non finite or negative averages can't claim any share of the population
+        let fitness = |s: &Species| {
+            if s.avg_fitness.is_finite() && s.avg_fitness > 0. {
+                s.avg_fitness
+            } else {
+                0.
+            }
+        };
+
+        let total_avg_fitness = self.species.iter().fold(0., |acc, s| acc + fitness(s));
+        let count = self.species.len() as f64;
+        let smoothing = self.sets.offspring_smoothing;
+
+        let shares = self
+            .species
+            .iter()
+            .map(|s| {
+                let share = if total_avg_fitness > 0. {
+                    fitness(s) / total_avg_fitness * pop_size as f64
+                } else {
+                    pop_size as f64 / count
+                };
+
+                // Newly founded species have no previous allocation to smooth against
+                if s.assigned_offspring == 0 {
+                    share
+                } else {
+                    smoothing * s.assigned_offspring as f64 + (1. - smoothing) * share
+                }
+            })
+            .collect::<Vec<f64>>();
+
+        let allocation = allocate(&shares, pop_size, self.sets.min_species_size);
+
+        for (species, amt) in self.species.iter_mut().zip(allocation) {
+            species.assigned_offspring = amt;
+        }
+    }
+
+    fn adjust_threshold(&mut self) {
+        let target = match self.sets.target_species {
+            Some(target) => target,
+            None => return,
+        };
+
+        let species = self.species.len();
+        let mut threshold = self.sets.speciation_threshold;
+
+        // Fewer species than wanted means genomes are being lumped together too
+        // easily, so the threshold is tightened, and vice versa
+        if species < target {
+            threshold -= self.sets.threshold_step;
+        } else if species > target {
+            threshold += self.sets.threshold_step;
+        }
+
+        self.sets.speciation_threshold =
+            threshold.clamp(self.sets.min_threshold, self.sets.max_threshold);
+    }
+
     fn speciate_population(&mut self) {
-        for species in &mut self.species {
-            species.genomes.clear();
+        let genomes = std::mem::take(&mut self.population);
+
+        // Distances from earlier generations may involve genomes that were edited since
+        self.distances.clear();
+
+        speciation::speciate(
+            &mut self.species,
+            genomes,
+            &self.sets,
+            &mut self.distances,
+            &mut self.next_species_id,
+            self.generations,
+        );
+
+        // Species no genome was assigned to have gone extinct
+        self.species.retain(|s| !s.genomes.is_empty());
+    }
+}
+
+// Splits total slots among species in proportion to their shares using largest
+// remainder rounding. As many species as fit, best first, are given at least
+// min slots; the ones that don't fit get none.
+fn allocate(shares: &[f64], total: usize, min: usize) -> Vec<usize> {
+    let mut allocation = vec![0; shares.len()];
+
+    let mut order = (0..shares.len()).collect::<Vec<usize>>();
+    order.sort_by(|&a, &b| shares[b].partial_cmp(&shares[a]).unwrap());
+
+    let guaranteed = total
+        .checked_div(min)
+        .map_or(order.len(), |fit| order.len().min(fit));
+    let order = &order[..guaranteed];
+
+    if order.is_empty() {
+        return allocation;
+    }
+
+    order.iter().for_each(|&i| allocation[i] = min);
+
+    let remaining = total - guaranteed * min;
+
+    let mut extra = order
+        .iter()
+        .map(|&i| (shares[i] - min as f64).max(0.))
+        .collect::<Vec<f64>>();
+    let mut total_extra = extra.iter().sum::<f64>();
+
+    if total_extra <= 0. {
+        extra.iter_mut().for_each(|e| *e = 1.);
+        total_extra = extra.len() as f64;
+    }
+
+    let quotas = extra
+        .iter()
+        .map(|e| e / total_extra * remaining as f64)
+        .collect::<Vec<f64>>();
+
+    let mut assigned = 0;
+
+    for (&i, quota) in order.iter().zip(&quotas) {
+        allocation[i] += quota.floor() as usize;
+        assigned += quota.floor() as usize;
+    }
+
+    let mut by_remainder = (0..order.len()).collect::<Vec<usize>>();
+    by_remainder.sort_by(|&a, &b| {
+        (quotas[b] - quotas[b].floor())
+            .partial_cmp(&(quotas[a] - quotas[a].floor()))
+            .unwrap()
+    });
+
+    for &j in by_remainder.iter().take(remaining - assigned) {
+        allocation[order[j]] += 1;
+    }
+
+    allocation
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::RepresentativeStrategy;
+
+    #[test]
+    fn allocation_sums_to_total() {
+        let allocation = allocate(&[7.3, 2.4, 0.3], 10, 0);
+
+        assert_eq!(allocation, vec![7, 3, 0]);
+    }
+
+    #[test]
+    fn allocation_minimum_size() {
+        assert_eq!(allocate(&[9.5, 0.5, 0.], 10, 2), vec![6, 2, 2]);
+        assert_eq!(allocate(&[9., 0.5, 0.5], 5, 2), vec![3, 2, 0]);
+    }
+
+    #[test]
+    fn allocation_without_fitness() {
+        assert_eq!(allocate(&[0., 0., 0.], 10, 0).iter().sum::<usize>(), 10);
+    }
+
+    #[test]
+    fn stagnation_actions_keep_pop_size() {
+        for action in &[
+            StagnationAction::DeltaCoding,
+            StagnationAction::Reinitialize,
+            StagnationAction::BoostMutation(2.),
+        ] {
+            let sets = Settings::new(2, 1, 30)
+                .population_stagnancy(2)
+                .stagnation_action(*action);
+            let mut pop = Population::new(sets);
+
+            for _ in 0..6 {
+                pop.next_generation();
+                assert_eq!(pop.get_citizens().len(), 30);
+            }
+        }
+    }
+
+    #[test]
+    fn stagnation_never_removes_every_species() {
+        let sets = Settings::new(2, 1, 30).allowed_stagnancy(0);
+        let mut pop = Population::new(sets);
+
+        pop.next_generation();
+
+        assert_eq!(pop.species.len(), 1);
+        assert_eq!(pop.get_citizens().len(), 30);
+    }
+
+    #[test]
+    fn lineage_links_children_to_parents() {
+        let sets = Settings::new(2, 1, 30).record_lineage(true);
+        let mut pop = Population::new(sets);
+
+        pop.next_generation();
+        pop.next_generation();
+
+        for birth in &pop.lineage {
+            assert!(!birth.parents.is_empty());
+            assert!(birth.parents.iter().all(|&p| p < birth.child));
+        }
+
+        assert!(pop
+            .get_species()
+            .iter()
+            .all(|s| s.fitness_history.len() as u64 == s.age + 1));
+    }
+
+    #[test]
+    fn pareto_front_tracks_objectives() {
+        let sets = Settings::new(2, 1, 30).multi_objective(true);
+        let mut pop = Population::new(sets);
+
+        for _ in 0..3 {
+            for (i, g) in pop.get_citizens().iter_mut().enumerate() {
+                g.add_objective(i as f64);
+                g.add_objective(-(i as f64));
+            }
+
+            pop.next_generation();
+        }
+
+        // Every generation scores the same 30 non dominated objectives
+        assert_eq!(pop.get_citizens().len(), 30);
+        assert_eq!(pop.pareto_front.len(), 30);
+    }
+
+    #[test]
+    fn pareto_front_thinned_by_crowding() {
+        let sets = Settings::new(2, 1, 30)
+            .multi_objective(true)
+            .pareto_front_size(10);
+        let mut pop = Population::new(sets);
+
+        for (i, g) in pop.get_citizens().iter_mut().enumerate() {
+            g.add_objective(i as f64);
+            g.add_objective(-(i as f64));
+        }
+
+        pop.next_generation();
+
+        let objectives = pop
+            .pareto_front
+            .iter()
+            .map(|g| g.objectives[0])
+            .collect::<Vec<f64>>();
+
+        // The extremes have infinite crowding distance and are always kept
+        assert_eq!(objectives.len(), 10);
+        assert!(objectives.contains(&0.) && objectives.contains(&29.));
+    }
+
+    #[test]
+    fn complexity_penalty_and_tiebreak() {
+        let sets = Settings::new(2, 1, 30)
+            .conn_penalty(0.1)
+            .size_tiebreak(true);
+        let mut pop = Population::new(sets);
+
+        for g in pop.get_citizens().iter_mut() {
+            g.add_fitness(1.);
+            g.adjusted_fitness = g.fitness;
+        }
+        pop.get_citizens()[29].conns[0].enabled = false;
+
+        pop.sort_population();
+        assert_eq!(pop.population[0].enabled_conns(), 2);
+
+        pop.apply_complexity_penalty();
+        assert!((pop.population[0].adjusted_fitness - 0.8).abs() < 1e-9);
+        assert!((pop.population[1].adjusted_fitness - 0.7).abs() < 1e-9);
+        assert!(pop.population.iter().all(|g| g.fitness == 1.));
+
+        // Unlike fitness, the score is not clamped at zero
+        pop.sets.conn_penalty = 1.;
+        pop.apply_complexity_penalty();
+        assert!(pop.population.iter().all(|g| g.adjusted_fitness < 0.));
+        assert!(pop.population.iter().all(|g| g.fitness == 1.));
+    }
+
+    #[test]
+    fn real_time_replacement() {
+        let sets = Settings::new(2, 1, 20)
+            .replacement_interval(2)
+            .min_replacement_age(10)
+            .record_lineage(true)
+            .innovation_matching(InnovationMatching::PerGeneration)
+            .prune_innovations(true);
+        let mut pop = Population::new(sets);
+        let mut replaced = Vec::<u64>::new();
+
+        for _ in 0..100 {
+            for genome in pop.get_citizens().iter_mut() {
+                let output = genome.feed_forward(&vec![1., 0.]).unwrap();
+                genome.fitness = output[0];
+            }
+
+            if let Some(id) = pop.tick() {
+                assert!(pop.get_citizens().iter().any(|g| g.id == id));
+                replaced.push(id);
+
+                // The worst genome leaves before breeding, so the parent is still
+                // a member of the species it was bred in
+                let birth = pop.lineage.last().unwrap();
+                let parent = pop
+                    .species
+                    .iter()
+                    .find(|s| s.genomes.iter().any(|g| g.id == birth.parents[0]))
+                    .unwrap();
+                assert_eq!(birth.species, Some(parent.id));
+            }
         }
 
-        'outer: for genome in self.population.drain(..) {
-            for species in &mut self.species {
-                if species.can_accomodate(&genome, &self.sets) {
-                    species.add_genome(genome);
-                    continue 'outer;
+        // Nobody is old enough to be replaced before tick 10
+        assert_eq!(replaced.len(), 46);
+        assert_eq!(pop.get_citizens().len(), 20);
+        assert_eq!(pop.generations, 2);
+
+        let best = pop.best_genome.as_ref().unwrap();
+        assert!(best.fitness > 0.);
+        assert_eq!(pop.best_fitness, best.fitness);
+
+        let members = pop.species.iter().map(|s| s.genomes.len()).sum::<usize>();
+        assert_eq!(members, 20);
+    }
+
+    #[test]
+    fn real_time_species_bookkeeping() {
+        let sets = Settings::new(2, 1, 10)
+            .replacement_interval(1)
+            .min_replacement_age(0)
+            .representative_strategy(RepresentativeStrategy::Champion);
+        let mut pop = Population::new(sets);
+
+        for tick in 1..=30 {
+            for genome in pop.get_citizens().iter_mut() {
+                genome.fitness = genome.id as f64;
+            }
+
+            pop.tick();
+
+            // Every 10 births a generation passes and the species are refreshed
+            if tick % 10 == 0 {
+                assert_eq!(pop.generations, tick / 10);
+                assert_eq!(pop.stats.species, pop.species.len());
+
+                for species in &pop.species {
+                    assert_eq!(species.representative().id, species.genomes[0].id);
+                    assert_eq!(species.age, pop.generations - 1 - species.created);
+                    assert_eq!(species.fitness_history.len() as u64, species.age + 1);
                 }
             }
+        }
+
+        assert!(pop.species.iter().any(|s| s.age == 2));
+    }
+
+    #[test]
+    fn ask_and_tell() {
+        let sets = Settings::new(2, 1, 20)
+            .innovation_matching(InnovationMatching::PerGeneration)
+            .prune_innovations(true);
+        let mut pop = Population::new(sets);
+
+        let mut workers = (0..30).map(|_| pop.ask()).collect::<Vec<(u64, Genome)>>();
+
+        for _ in 0..200 {
+            let (id, genome) = workers.remove(0);
+            let output = genome.feed_forward(&vec![1., 0.]).unwrap();
+
+            pop.tell(id, output[0]).unwrap();
+            assert!(pop.get_citizens().len() <= 20);
+
+            workers.push(pop.ask());
+        }
+
+        // Asks beyond the initial 20 citizens breed, except the 10 made before any
+        // genome was evaluated
+        assert_eq!(pop.generations, 10);
+        assert_eq!(pop.get_citizens().len(), 20);
+        assert!(pop.best_fitness > 0.);
+        assert!(pop.tell(workers[0].0 + 1000, 1.).is_err());
+        assert!(pop.tell(workers[0].0, 1.).is_ok());
+        assert!(pop.tell(workers[0].0, 1.).is_err());
+    }
+
+    #[test]
+    fn ask_and_tell_species_bookkeeping() {
+        let mut pop = Population::new(Settings::new(2, 1, 10));
+
+        let (first, _) = pop.ask();
+        pop.next_generation();
+        assert_eq!(pop.generations, 0);
+        pop.tell(first, 1.).unwrap();
 
-            let new_spec = Species::new(genome);
-            self.species.push(new_spec);
+        for i in 0..40 {
+            let (id, _) = pop.ask();
+            pop.tell(id, i as f64).unwrap();
         }
+
+        // The 10 initial citizens are handed out first, the other 31 asks breed
+        assert_eq!(pop.generations, 3);
+        assert_eq!(pop.stats.generation, 3);
+        assert_eq!(pop.stats.species, pop.species.len());
+        assert_eq!(pop.stats.best_fitness, pop.best_fitness);
+        assert!(pop.species.iter().any(|s| s.age == 2));
+        assert!(pop
+            .species
+            .iter()
+            .all(|s| s.fitness_history.len() as u64 == s.age + 1));
+    }
+
+    #[test]
+    fn elites_carried_forward() {
+        let sets = Settings::new(2, 1, 30)
+            .speciation_threshold(100.)
+            .species_elites(3)
+            .elitism_min_size(1)
+            .global_elitism(true);
+        let mut pop = Population::new(sets);
+
+        for (i, g) in pop.get_citizens().iter_mut().enumerate() {
+            g.add_fitness(i as f64);
+        }
+
+        let mut best = pop
+            .get_citizens()
+            .iter()
+            .rev()
+            .take(3)
+            .cloned()
+            .collect::<Vec<Genome>>();
+        best.reverse();
+
+        pop.next_generation();
+
+        // The global champion and the three species elites, the champion copied once
+        let citizens = pop.get_citizens();
+        assert_eq!(citizens.len(), 30);
+
+        for elite in &best {
+            let copies = citizens
+                .iter()
+                .filter(|g| g.id == elite.id)
+                .collect::<Vec<&Genome>>();
+
+            assert!(!copies.is_empty());
+            assert!(copies
+                .iter()
+                .all(|g| g.conns == elite.conns && g.fitness == 0.));
+            assert!(copies.iter().all(|g| g
+                .conns
+                .iter()
+                .zip(&elite.conns)
+                .all(|(a, b)| a.weight == b.weight)));
+        }
+
+        assert_eq!(citizens.iter().filter(|g| g.id == best[2].id).count(), 1);
+    }
+
+    #[test]
+    fn global_elite_is_best_genome() {
+        let sets = Settings::new(2, 1, 20)
+            .speciation_threshold(100.)
+            .species_elites(0)
+            .global_elitism(true);
+        let mut pop = Population::new(sets);
+
+        pop.get_citizens()[3].add_fitness(10.);
+        pop.next_generation();
+
+        let best = pop.best_genome.clone().unwrap();
+
+        // A weaker generation must not displace the all-time champion
+        pop.get_citizens()
+            .iter_mut()
+            .filter(|g| g.id != best.id)
+            .for_each(|g| g.add_fitness(1.));
+        pop.next_generation();
+
+        let citizens = pop.get_citizens();
+        assert_eq!(citizens.iter().filter(|g| g.id == best.id).count(), 1);
+        assert!(citizens
+            .iter()
+            .any(|g| g.id == best.id && g.conns == best.conns));
+    }
+
+    #[test]
+    fn threshold_steered_toward_target() {
+        let thresholds = |sets: Settings| {
+            let mut pop = Population::new(sets);
+
+            (0..4)
+                .map(|_| {
+                    pop.next_generation();
+                    pop.stats.speciation_threshold
+                })
+                .collect::<Vec<f64>>()
+        };
+
+        // 30 genomes can never make up 100 species, nor fewer than 0
+        let more = Settings::new(2, 1, 30)
+            .target_species(100)
+            .threshold_step(0.5)
+            .min_threshold(2.);
+        assert_eq!(thresholds(more), vec![3., 2.5, 2., 2.]);
+
+        let fewer = Settings::new(2, 1, 30)
+            .target_species(0)
+            .threshold_step(0.5)
+            .max_threshold(4.);
+        assert_eq!(thresholds(fewer), vec![3., 3.5, 4., 4.]);
+    }
+
+    #[test]
+    fn raw_fitness_survives_generation() {
+        let mut pop = Population::new(Settings::new(2, 1, 30));
+
+        for g in pop.get_citizens().iter_mut() {
+            g.add_fitness(g.id as f64);
+        }
+
+        pop.next_generation();
+
+        assert_eq!(pop.best_fitness, 29.);
+        assert_eq!(pop.best_genome.as_ref().unwrap().fitness, 29.);
+
+        // Species keep their culled members from the evaluated generation
+        for species in pop.get_species() {
+            for genome in &species.genomes {
+                assert_eq!(genome.fitness, genome.id as f64);
+                assert!(genome.adjusted_fitness <= genome.fitness);
+            }
+        }
+    }
+
+    #[test]
+    fn novelty_leaves_raw_fitness() {
+        let sets = Settings::new(2, 1, 30).novelty_weight(0.5).novelty_k(3);
+        let mut pop = Population::new(sets);
+
+        // The least fit genomes behave the most unusually
+        for g in pop.get_citizens().iter_mut() {
+            g.add_fitness(g.id as f64);
+            g.set_behavior(vec![(30 - g.id).pow(2) as f64]);
+        }
+
+        pop.next_generation();
+
+        assert_eq!(pop.best_fitness, 29.);
+        assert_eq!(pop.best_genome.as_ref().unwrap().fitness, 29.);
+
+        for species in pop.get_species() {
+            for genome in &species.genomes {
+                assert_eq!(genome.fitness, genome.id as f64);
+                assert!(genome.novelty > 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn pareto_progress_prevents_stagnation() {
+        let sets = Settings::new(2, 1, 30)
+            .multi_objective(true)
+            .pareto_front_size(1000)
+            .allowed_stagnancy(3)
+            .population_stagnancy(3);
+        let mut pop = Population::new(sets);
+
+        // Every newborn widens the Pareto front without any fitness being evaluated
+        for _ in 0..10 {
+            for g in pop.get_citizens().iter_mut() {
+                g.add_objective(g.id as f64);
+                g.add_objective(-(g.id as f64));
+            }
+
+            pop.next_generation();
+            assert_eq!(pop.stagnancy, 0);
+            assert!(pop.get_species().iter().all(|s| s.stagnancy < 3));
+        }
+
+        assert!(pop.best_genome.is_some());
+        assert_eq!(pop.best_fitness, 0.);
+        assert!(pop
+            .get_species()
+            .iter()
+            .flat_map(|s| s.genomes.iter())
+            .all(|g| g.fitness == 0.));
     }
 }
@@ -1,3 +1,72 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrossoverMode {
+    // Each matching gene is copied wholesale from a randomly chosen parent
+    Random,
+    // Matching genes take the mean of both parents' weights
+    Average,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionStrategy {
+    // Fitness proportionate selection
+    Roulette,
+    // Fittest of k genomes drawn uniformly
+    Tournament(usize),
+    // Linear rank based selection, the best genome is n times as likely as the worst
+    Rank,
+    // Every surviving genome is equally likely
+    Uniform,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RepresentativeStrategy {
+    // Random member of the species from the previous generation
+    Random,
+    // Fittest member of the species
+    Champion,
+    // Member with the least total distance to the rest of the species
+    Centroid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeneNormalization {
+    // Divide by the gene count of the larger genome, unless it has fewer genes
+    // than the given cutoff
+    Cutoff(usize),
+    // Always divide by the gene count of the larger genome
+    Always,
+    // Never normalize disjoint and excess gene counts
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FitnessSharing {
+    // Divide fitness by the size of the species
+    SpeciesSize,
+    // Divide fitness by the size of the species, but by no less than the given size
+    MinSpeciesSize(usize),
+    // Min-max normalize fitness over the whole population, as NEAT-Python does
+    Normalized,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StagnationAction {
+    // Only the two best species are allowed to reproduce, as in the NEAT paper
+    DeltaCoding,
+    // Start over from a fresh population that still contains the champion
+    Reinitialize,
+    // Multiply the mutation rates by the given factor until fitness improves
+    BoostMutation(f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InnovationMatching {
+    // The same structural mutation always gets the same innovation number
+    Global,
+    // Structural mutations are only matched within a generation, as in the NEAT paper
+    PerGeneration,
+}
+
 pub struct Settings {
     pub pop_size: u32,
     pub inputs: u32,
@@ -11,12 +80,56 @@ pub struct Settings {
     pub off_gene_on_rate: f64,
     pub off_in_both_on_rate: f64,
     pub only_mut_rate: f64,
+    pub innovation_matching: InnovationMatching,
+    pub prune_innovations: bool,
+    pub crossover_mode: CrossoverMode,
+    pub tie_inherit_both: bool,
+    pub selection_strategy: SelectionStrategy,
 
     pub disjoint_coeff: f64,
     pub excess_coeff: f64,
     pub weight_coeff: f64,
+    pub gene_normalization: GeneNormalization,
+    pub require_matching_genes: bool,
+    pub speciation_threads: usize,
     pub speciation_threshold: f64,
+    pub representative_strategy: RepresentativeStrategy,
+    pub target_species: Option<usize>,
+    pub threshold_step: f64,
+    pub min_threshold: f64,
+    pub max_threshold: f64,
     pub allowed_stagnancy: u32,
+    pub species_elitism: usize,
+    pub fitness_sharing: FitnessSharing,
+    pub young_age_threshold: u64,
+    pub young_fitness_boost: f64,
+    pub old_age_threshold: u64,
+    pub old_fitness_penalty: f64,
+    pub population_stagnancy: Option<u32>,
+    pub stagnation_action: StagnationAction,
+
+    pub survival_threshold: f64,
+    pub min_species_size: usize,
+    pub offspring_smoothing: f64,
+    pub elitism_min_size: usize,
+    pub species_elites: usize,
+    pub global_elitism: bool,
+
+    pub novelty_weight: f64,
+    pub novelty_k: usize,
+    pub archive_threshold: f64,
+    pub archive_size: usize,
+    pub multi_objective: bool,
+    pub pareto_front_size: usize,
+
+    pub conn_penalty: f64,
+    pub node_penalty: f64,
+    pub size_tiebreak: bool,
+
+    pub replacement_interval: u64,
+    pub min_replacement_age: u64,
+
+    pub record_lineage: bool,
 }
 
 impl Settings {
@@ -32,11 +145,50 @@ impl Settings {
             off_gene_on_rate: 0.25,
             off_in_both_on_rate: 0.01,
             only_mut_rate: 0.25,
+            innovation_matching: InnovationMatching::Global,
+            prune_innovations: false,
+            crossover_mode: CrossoverMode::Random,
+            tie_inherit_both: false,
+            selection_strategy: SelectionStrategy::Roulette,
             disjoint_coeff: 1.,
             excess_coeff: 1.,
             weight_coeff: 0.4,
+            gene_normalization: GeneNormalization::Cutoff(20),
+            require_matching_genes: true,
+            speciation_threads: 1,
             speciation_threshold: 3.,
+            representative_strategy: RepresentativeStrategy::Random,
+            target_species: None,
+            threshold_step: 0.3,
+            min_threshold: 0.3,
+            max_threshold: 10.,
             allowed_stagnancy: 15,
+            species_elitism: 0,
+            fitness_sharing: FitnessSharing::SpeciesSize,
+            young_age_threshold: 10,
+            young_fitness_boost: 1.,
+            old_age_threshold: 30,
+            old_fitness_penalty: 1.,
+            population_stagnancy: None,
+            stagnation_action: StagnationAction::DeltaCoding,
+            survival_threshold: 0.5,
+            min_species_size: 0,
+            offspring_smoothing: 0.,
+            elitism_min_size: 4,
+            species_elites: 1,
+            global_elitism: false,
+            novelty_weight: 0.,
+            novelty_k: 15,
+            archive_threshold: 1.,
+            archive_size: 500,
+            multi_objective: false,
+            pareto_front_size: 100,
+            conn_penalty: 0.,
+            node_penalty: 0.,
+            size_tiebreak: false,
+            replacement_interval: 20,
+            min_replacement_age: 50,
+            record_lineage: false,
         }
     }
 
@@ -75,6 +227,32 @@ impl Settings {
         self
     }
 
+    pub fn innovation_matching(mut self, matching: InnovationMatching) -> Self {
+        self.innovation_matching = matching;
+        self
+    }
+
+    // Forget innovations that have died out of the population every generation
+    pub fn prune_innovations(mut self, prune: bool) -> Self {
+        self.prune_innovations = prune;
+        self
+    }
+
+    pub fn crossover_mode(mut self, mode: CrossoverMode) -> Self {
+        self.crossover_mode = mode;
+        self
+    }
+
+    pub fn tie_inherit_both(mut self, inherit: bool) -> Self {
+        self.tie_inherit_both = inherit;
+        self
+    }
+
+    pub fn selection_strategy(mut self, strategy: SelectionStrategy) -> Self {
+        self.selection_strategy = strategy;
+        self
+    }
+
     pub fn disjoint_coeff(mut self, coeff: f64) -> Self {
         self.disjoint_coeff = coeff;
         self
@@ -90,13 +268,201 @@ impl Settings {
         self
     }
 
+    pub fn gene_normalization(mut self, normalization: GeneNormalization) -> Self {
+        self.gene_normalization = normalization;
+        self
+    }
+
+    pub fn require_matching_genes(mut self, require: bool) -> Self {
+        self.require_matching_genes = require;
+        self
+    }
+
+    // 0 uses all available cores
+    pub fn speciation_threads(mut self, threads: usize) -> Self {
+        self.speciation_threads = threads;
+        self
+    }
+
     pub fn speciation_threshold(mut self, threshold: f64) -> Self {
         self.speciation_threshold = threshold;
         self
     }
 
+    pub fn representative_strategy(mut self, strategy: RepresentativeStrategy) -> Self {
+        self.representative_strategy = strategy;
+        self
+    }
+
+    pub fn target_species(mut self, target: usize) -> Self {
+        self.target_species = Some(target);
+        self
+    }
+
+    pub fn threshold_step(mut self, step: f64) -> Self {
+        self.threshold_step = step;
+        self
+    }
+
+    pub fn min_threshold(mut self, threshold: f64) -> Self {
+        self.min_threshold = threshold;
+        self
+    }
+
+    pub fn max_threshold(mut self, threshold: f64) -> Self {
+        self.max_threshold = threshold;
+        self
+    }
+
     pub fn allowed_stagnancy(mut self, stagnancy: u32) -> Self {
         self.allowed_stagnancy = stagnancy;
         self
     }
+
+    pub fn species_elitism(mut self, elitism: usize) -> Self {
+        self.species_elitism = elitism;
+        self
+    }
+
+    pub fn population_stagnancy(mut self, stagnancy: u32) -> Self {
+        self.population_stagnancy = Some(stagnancy);
+        self
+    }
+
+    pub fn stagnation_action(mut self, action: StagnationAction) -> Self {
+        self.stagnation_action = action;
+        self
+    }
+
+    pub fn fitness_sharing(mut self, sharing: FitnessSharing) -> Self {
+        self.fitness_sharing = sharing;
+        self
+    }
+
+    // Species younger than this many generations get their fitness boosted
+    pub fn young_age_threshold(mut self, age: u64) -> Self {
+        self.young_age_threshold = age;
+        self
+    }
+
+    pub fn young_fitness_boost(mut self, boost: f64) -> Self {
+        self.young_fitness_boost = boost;
+        self
+    }
+
+    // Species older than this many generations get their fitness penalized
+    pub fn old_age_threshold(mut self, age: u64) -> Self {
+        self.old_age_threshold = age;
+        self
+    }
+
+    pub fn old_fitness_penalty(mut self, penalty: f64) -> Self {
+        self.old_fitness_penalty = penalty;
+        self
+    }
+
+    pub fn survival_threshold(mut self, threshold: f64) -> Self {
+        self.survival_threshold = threshold;
+        self
+    }
+
+    pub fn min_species_size(mut self, size: usize) -> Self {
+        self.min_species_size = size;
+        self
+    }
+
+    // Weight of a species' previous offspring count against its fitness share
+    pub fn offspring_smoothing(mut self, smoothing: f64) -> Self {
+        self.offspring_smoothing = smoothing;
+        self
+    }
+
+    pub fn elitism_min_size(mut self, size: usize) -> Self {
+        self.elitism_min_size = size;
+        self
+    }
+
+    pub fn species_elites(mut self, elites: usize) -> Self {
+        self.species_elites = elites;
+        self
+    }
+
+    pub fn global_elitism(mut self, elitism: bool) -> Self {
+        self.global_elitism = elitism;
+        self
+    }
+
+    // Share of novelty in the fitness used for selection, 0 disables novelty search
+    // and 1 selects on novelty alone. The best genome is still tracked by fitness.
+    pub fn novelty_weight(mut self, weight: f64) -> Self {
+        self.novelty_weight = weight;
+        self
+    }
+
+    pub fn novelty_k(mut self, k: usize) -> Self {
+        self.novelty_k = k;
+        self
+    }
+
+    // Novelty above which a behavior is added to the archive
+    pub fn archive_threshold(mut self, threshold: f64) -> Self {
+        self.archive_threshold = threshold;
+        self
+    }
+
+    pub fn archive_size(mut self, size: usize) -> Self {
+        self.archive_size = size;
+        self
+    }
+
+    // Select on Pareto rank and crowding distance of the Genome objectives instead of
+    // fitness. The best genome is still tracked by fitness, while Species and the
+    // Population also count genomes newly entering the Pareto front as progress.
+    pub fn multi_objective(mut self, multi: bool) -> Self {
+        self.multi_objective = multi;
+        self
+    }
+
+    // Most genomes kept in the Pareto front, the most crowded ones are dropped first
+    pub fn pareto_front_size(mut self, size: usize) -> Self {
+        self.pareto_front_size = size;
+        self
+    }
+
+    // Selection score subtracted per enabled connection before sharing
+    pub fn conn_penalty(mut self, penalty: f64) -> Self {
+        self.conn_penalty = penalty;
+        self
+    }
+
+    // Selection score subtracted per hidden node before sharing
+    pub fn node_penalty(mut self, penalty: f64) -> Self {
+        self.node_penalty = penalty;
+        self
+    }
+
+    // Rank the smaller of two Genomes with equal selection score first, both when
+    // sorting the Population and when picking the fitter parent in crossover
+    pub fn size_tiebreak(mut self, tiebreak: bool) -> Self {
+        self.size_tiebreak = tiebreak;
+        self
+    }
+
+    // Ticks between two replacements in real time evolution
+    pub fn replacement_interval(mut self, interval: u64) -> Self {
+        self.replacement_interval = interval;
+        self
+    }
+
+    // Ticks a Genome must have lived before real time evolution may replace it, so
+    // it has had time to be evaluated
+    pub fn min_replacement_age(mut self, age: u64) -> Self {
+        self.min_replacement_age = age;
+        self
+    }
+
+    pub fn record_lineage(mut self, record: bool) -> Self {
+        self.record_lineage = record;
+        self
+    }
 }
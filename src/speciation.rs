@@ -0,0 +1,256 @@
+use crate::genome::Genome;
+use crate::settings::Settings;
+use crate::species::Species;
+
+use std::collections::HashMap;
+use std::thread;
+use std::vec::Vec;
+
+// Memoized distances between genomes, keyed by their IDs. Distances are symmetric,
+// so each pair is only computed once. The cache is meant to live for a single
+// generation, during which genomes with the same ID share the same genes.
+#[derive(Default)]
+pub struct DistanceCache {
+    distances: HashMap<(u64, u64), f64>,
+}
+
+impl DistanceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key(a: &Genome, b: &Genome) -> (u64, u64) {
+        if a.id < b.id {
+            (a.id, b.id)
+        } else {
+            (b.id, a.id)
+        }
+    }
+
+    pub fn lookup(&self, a: &Genome, b: &Genome) -> Option<f64> {
+        self.distances.get(&Self::key(a, b)).copied()
+    }
+
+    pub fn insert(&mut self, a: &Genome, b: &Genome, distance: f64) {
+        self.distances.insert(Self::key(a, b), distance);
+    }
+
+    pub fn get(&mut self, a: &Genome, b: &Genome, sets: &Settings) -> f64 {
+        *self
+            .distances
+            .entry(Self::key(a, b))
+            .or_insert_with(|| a.distance(b, sets))
+    }
+
+    pub fn len(&self) -> usize {
+        self.distances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.distances.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.distances.clear();
+    }
+}
+
+// First compatible representative of a genome and the distances computed to find it
+struct Match {
+    found: Option<usize>,
+    computed: Vec<(usize, f64)>,
+}
+
+enum Assignment {
+    Existing(usize),
+    New(usize),
+}
+
+// Index of the first representative every genome is compatible with, along with the
+// distances that were missing from the cache. Genomes are split across threads when
+// more than one is configured.
+fn match_representatives(
+    reprs: &[&Genome],
+    genomes: &[Genome],
+    sets: &Settings,
+    cache: &DistanceCache,
+) -> Vec<Match> {
+    let threshold = sets.speciation_threshold;
+    let first = |g: &Genome| {
+        let mut computed = Vec::new();
+        let found = reprs.iter().enumerate().position(|(i, r)| {
+            let distance = cache.lookup(r, g).unwrap_or_else(|| {
+                let distance = r.distance(g, sets);
+                computed.push((i, distance));
+                distance
+            });
+
+            distance < threshold
+        });
+
+        Match { found, computed }
+    };
+
+    let threads = match sets.speciation_threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+
+    if threads <= 1 || reprs.is_empty() || genomes.len() < 2 {
+        return genomes.iter().map(first).collect();
+    }
+
+    let chunk_size = genomes.len().div_ceil(threads);
+
+    thread::scope(|scope| {
+        let handles = genomes
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(first).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+// Distributes the genomes among the existing species, founding new species for
+// genomes that fit none of them. Each genome joins the first compatible species.
+// Every distance computed is kept in the cache for the rest of the generation.
+pub fn speciate(
+    species: &mut Vec<Species>,
+    genomes: Vec<Genome>,
+    sets: &Settings,
+    cache: &mut DistanceCache,
+    next_id: &mut u64,
+    generation: u64,
+) {
+    for s in species.iter_mut() {
+        s.genomes.clear();
+    }
+
+    let threshold = sets.speciation_threshold;
+
+    let reprs = species
+        .iter()
+        .map(|s| s.representative())
+        .collect::<Vec<&Genome>>();
+    let matches = match_representatives(&reprs, &genomes, sets, cache);
+
+    let mut founders = Vec::<usize>::new();
+    let mut assignments = Vec::<Assignment>::with_capacity(genomes.len());
+
+    for (i, Match { found, computed }) in matches.into_iter().enumerate() {
+        for (r, distance) in computed {
+            cache.insert(reprs[r], &genomes[i], distance);
+        }
+
+        if let Some(s) = found {
+            assignments.push(Assignment::Existing(s));
+            continue;
+        }
+
+        let compatible = founders
+            .iter()
+            .position(|&f| cache.get(&genomes[f], &genomes[i], sets) < threshold);
+
+        match compatible {
+            Some(s) => assignments.push(Assignment::New(s)),
+            None => {
+                assignments.push(Assignment::New(founders.len()));
+                founders.push(i);
+            }
+        }
+    }
+
+    let existing = species.len();
+
+    for (genome, assignment) in genomes.into_iter().zip(assignments) {
+        match assignment {
+            Assignment::Existing(s) => species[s].add_genome(genome),
+            Assignment::New(s) if existing + s == species.len() => {
+                species.push(Species::new(genome, *next_id, generation));
+                *next_id += 1;
+            }
+            Assignment::New(s) => species[existing + s].add_genome(genome),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history::History;
+
+    fn generation() -> Vec<Genome> {
+        let mut hist = History::new(3, 2);
+        let sets = Settings::new(3, 2, 40)
+            .node_mut_rate(0.5)
+            .conn_mut_rate(0.5);
+
+        (0..40)
+            .map(|id| {
+                let mut genome = Genome::new(3, 2, false);
+                genome.id = id;
+                genome.mutate(&mut hist, &sets);
+                genome
+            })
+            .collect()
+    }
+
+    #[test]
+    fn threaded_matches_serial() {
+        let genomes = generation();
+
+        let sizes = |threads: usize| {
+            let sets = Settings::new(3, 2, 40)
+                .speciation_threshold(0.5)
+                .speciation_threads(threads);
+            let mut species = Vec::<Species>::new();
+
+            speciate(
+                &mut species,
+                genomes.clone(),
+                &sets,
+                &mut DistanceCache::new(),
+                &mut 0,
+                0,
+            );
+
+            species
+                .iter()
+                .map(|s| s.genomes.len())
+                .collect::<Vec<usize>>()
+        };
+
+        assert_eq!(sizes(1), sizes(4));
+        assert_eq!(sizes(1).iter().sum::<usize>(), 40);
+    }
+
+    #[test]
+    fn distances_cached_for_the_generation() {
+        let genomes = generation();
+        let sets = Settings::new(3, 2, 40).speciation_threshold(0.5);
+        let mut species = Vec::<Species>::new();
+        let mut cache = DistanceCache::new();
+
+        speciate(&mut species, genomes.clone(), &sets, &mut cache, &mut 0, 0);
+
+        let cached = cache.len();
+        assert!(cached > 0);
+
+        for a in &genomes {
+            for b in &genomes {
+                if let Some(distance) = cache.lookup(a, b) {
+                    assert_eq!(distance, a.distance(b, &sets));
+                }
+            }
+        }
+
+        // The founders are now the representatives, so the only new distances are
+        // those between each founder and itself
+        speciate(&mut species, genomes, &sets, &mut cache, &mut 0, 1);
+        assert_eq!(cache.len(), cached + species.len());
+    }
+}
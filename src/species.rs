@@ -1,5 +1,6 @@
 use crate::genome::Genome;
-use crate::settings::Settings;
+use crate::settings::{FitnessSharing, RepresentativeStrategy, SelectionStrategy, Settings};
+use crate::speciation::DistanceCache;
 
 use rand::seq::SliceRandom;
 use rand::thread_rng;
@@ -9,8 +10,12 @@ use std::fmt;
 use std::vec::Vec;
 
 pub struct Species {
+    pub id: u64,
+    pub created: u64, // Generation this Species was founded in
+    pub age: u64,
+    pub fitness_history: Vec<f64>, // Champion fitness of every generation lived
     pub genomes: Vec<Genome>,
-    max_fitness: f64,
+    pub max_fitness: f64,
     pub avg_fitness: f64,
     pub stagnancy: u32,
     representative: Genome,
@@ -19,7 +24,9 @@ pub struct Species {
 
 impl fmt::Debug for Species {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut res = String::from(&format!("Genomes: {}\n", self.genomes.len()));
+        let mut res = String::from(&format!("Species: {}\n", self.id));
+        res += &format!("age: {}\n", self.age);
+        res += &format!("Genomes: {}\n", self.genomes.len());
         res += &format!("max_fitness: {}\n", self.max_fitness);
         res += &format!("representative: {:?}", self.representative);
 
@@ -28,116 +35,148 @@ impl fmt::Debug for Species {
 }
 
 impl Species {
-    pub fn new(head: Genome) -> Self {
+    pub fn new(head: Genome, id: u64, generation: u64) -> Self {
         let max_fitness = head.fitness;
         let avg_fitness = head.fitness;
         let repr = head.clone();
 
-        let species = Self {
+        Self {
+            id,
+            created: generation,
+            age: 0,
+            fitness_history: Vec::new(),
             genomes: vec![head],
             max_fitness,
             avg_fitness,
             stagnancy: 0,
             representative: repr,
             assigned_offspring: 0,
-        };
-
-        species
+        }
     }
 
-    pub fn can_accomodate(&self, gen: &Genome, sets: &Settings) -> bool {
-        let repr_genes = &self.representative.conns;
-        let new_genes = &gen.conns;
+    pub fn representative(&self) -> &Genome {
+        &self.representative
+    }
 
-        let max_repr = repr_genes.iter().max_by_key(|g| g.innov).unwrap();
-        let max_new = new_genes.iter().max_by_key(|g| g.innov).unwrap();
+    pub fn update_representative(&mut self, sets: &Settings, cache: &mut DistanceCache) {
+        if self.genomes.is_empty() {
+            return;
+        }
 
-        let (mut genes1, mut genes2) = if max_repr.innov > max_new.innov {
-            (repr_genes.iter(), new_genes.iter())
-        } else {
-            (new_genes.iter(), repr_genes.iter())
-        };
+        let repr = match sets.representative_strategy {
+            RepresentativeStrategy::Random => self.genomes.choose(&mut thread_rng()).unwrap(),
+            RepresentativeStrategy::Champion => &self.genomes[0],
+            RepresentativeStrategy::Centroid => {
+                let genomes = &self.genomes;
+                let len = genomes.len();
 
-        let mut p1 = genes1.next();
-        let mut p2 = genes2.next();
-
-        let mut disjoint_genes = 0.;
-        let mut excess_genes = 0.;
-        let mut weight_difference = 0.;
-        let mut matching_genes = 0.;
-
-        while p1.is_some() {
-            if let (Some(g1), Some(g2)) = (p1, p2) {
-                if g1.innov == g2.innov {
-                    matching_genes += 1.;
-                    weight_difference += (g1.weight - g2.weight).abs();
-                    p1 = genes1.next();
-                    p2 = genes2.next();
-                } else if g2.innov > g1.innov {
-                    disjoint_genes += 1.;
-                    p1 = genes1.next();
-                } else {
-                    disjoint_genes += 1.;
-                    p2 = genes2.next();
-                }
+                let totals = (0..len)
+                    .map(|i| {
+                        (0..len).fold(0., |acc, j| acc + cache.get(&genomes[i], &genomes[j], sets))
+                    })
+                    .collect::<Vec<f64>>();
 
-                continue;
-            }
+                let centroid = (0..len)
+                    .min_by(|&a, &b| totals[a].partial_cmp(&totals[b]).unwrap())
+                    .unwrap();
 
-            if let (Some(_), None) = (p1, p2) {
-                excess_genes += 1.;
-                p1 = genes1.next();
+                &self.genomes[centroid]
             }
-        }
-
-        if matching_genes == 0. {
-            return false;
-        }
-
-        let mut n = if repr_genes.len() > new_genes.len() {
-            repr_genes.len() as f64
-        } else {
-            new_genes.len() as f64
         };
 
-        n = if n < 20. { 1. } else { n };
-
-        let delta = (sets.disjoint_coeff * disjoint_genes / n)
-            + (sets.excess_coeff * excess_genes / n)
-            + (sets.weight_coeff * weight_difference / matching_genes);
-
-        delta < sets.speciation_threshold
+        self.representative = repr.clone();
     }
 
-    pub fn fitness_sharing(&mut self) {
+    // Shares the selection score every member holds in its adjusted fitness, leaving
+    // the raw fitness untouched. min_fitness and max_fitness are the score bounds of
+    // the whole population.
+    pub fn fitness_sharing(&mut self, sets: &Settings, min_fitness: f64, max_fitness: f64) {
         let len = self.genomes.len() as f64;
 
+        let adjust = |fitness: f64| match sets.fitness_sharing {
+            FitnessSharing::SpeciesSize => fitness / len,
+            FitnessSharing::MinSpeciesSize(min) => fitness / len.max(min as f64),
+            FitnessSharing::Normalized => {
+                (fitness - min_fitness) / (max_fitness - min_fitness).max(1.)
+            }
+        };
+
+        // Young species are given a head start to protect innovation, while old
+        // ones are made to give way
+        let age_factor = if self.age < sets.young_age_threshold {
+            sets.young_fitness_boost
+        } else if self.age > sets.old_age_threshold {
+            sets.old_fitness_penalty
+        } else {
+            1.
+        };
+
         self.genomes
             .iter_mut()
-            .for_each(|g| g.fitness = g.fitness / len);
+            .for_each(|g| g.adjusted_fitness = adjust(g.adjusted_fitness) * age_factor);
 
-        let total_fitness = self.genomes.iter().fold(0., |acc, g| acc + g.fitness);
+        let total_fitness = self
+            .genomes
+            .iter()
+            .fold(0., |acc, g| acc + g.adjusted_fitness);
 
         self.avg_fitness = total_fitness / len;
     }
 
-    fn select_parent(&self) -> &Genome {
-        let total_fitness = self.genomes.iter().fold(0., |acc, g| acc + g.fitness);
-
+    // Picks a parent by the shared selection score. Negative scores give a genome no
+    // chance in roulette selection.
+    fn select_parent(&self, sets: &Settings) -> &Genome {
         let mut rng = thread_rng();
 
-        let threshold = rng.gen_range(0., total_fitness);
+        let weight = |g: &Genome| g.adjusted_fitness.max(0.);
+        let total_fitness = self.genomes.iter().fold(0., |acc, g| acc + weight(g));
+
+        // With no fitness to go by (e.g. every genome scored zero) the fitness based
+        // strategies have nothing to prefer, so every genome is equally likely
+        let strategy = match sets.selection_strategy {
+            SelectionStrategy::Tournament(_) => sets.selection_strategy,
+            _ if total_fitness <= 0. || !total_fitness.is_finite() => SelectionStrategy::Uniform,
+            strategy => strategy,
+        };
+
+        match strategy {
+            SelectionStrategy::Roulette => {
+                let threshold = rng.gen_range(0., total_fitness);
+
+                let mut current = 0.;
 
-        let mut current = 0.;
+                for genome in &self.genomes {
+                    current += weight(genome);
+                    if current > threshold {
+                        return genome;
+                    }
+                }
 
-        for genome in &self.genomes {
-            current += genome.fitness;
-            if current > threshold {
-                return genome;
+                &self.genomes[0]
             }
-        }
+            SelectionStrategy::Tournament(k) => (0..k.max(1))
+                .map(|_| self.genomes.choose(&mut rng).unwrap())
+                .max_by(|a, b| a.adjusted_fitness.partial_cmp(&b.adjusted_fitness).unwrap())
+                .unwrap(),
+            SelectionStrategy::Rank => {
+                // Genomes are sorted by descending score, so the genome at index i
+                // gets a weight of (len - i)
+                let len = self.genomes.len();
+                let threshold = rng.gen_range(0, len * (len + 1) / 2);
+
+                let mut current = 0;
+
+                for (i, genome) in self.genomes.iter().enumerate() {
+                    current += len - i;
+                    if current > threshold {
+                        return genome;
+                    }
+                }
 
-        &self.genomes[0]
+                &self.genomes[0]
+            }
+            SelectionStrategy::Uniform => self.genomes.choose(&mut rng).unwrap(),
+        }
     }
 
     pub fn produce_offspring(&self, amt: usize, sets: &Settings) -> Vec<Genome> {
@@ -147,10 +186,14 @@ impl Species {
 
         for _ in 0..amt {
             if rng.gen::<f64>() < sets.only_mut_rate {
-                offspring.push((*self.genomes.choose(&mut rng).unwrap()).clone())
+                let parent = self.genomes.choose(&mut rng).unwrap();
+                let mut child = parent.clone();
+                child.parents = vec![parent.id];
+                child.mutations.clear();
+                offspring.push(child);
             } else {
-                let parent1 = self.select_parent();
-                let parent2 = self.select_parent();
+                let parent1 = self.select_parent(sets);
+                let parent2 = self.select_parent(sets);
 
                 offspring.push(Genome::crossover(parent1, parent2, sets));
             }
@@ -159,13 +202,31 @@ impl Species {
         offspring
     }
 
-    pub fn update_stagnancy(&mut self) {
-        if self.genomes.len() == 0 {
-            self.stagnancy = u32::MAX;
-            return;
+    // Highest raw fitness among the members. Members are ordered by selection score,
+    // so the first one isn't necessarily the fittest.
+    fn champion_fitness(&self) -> Option<f64> {
+        self.genomes
+            .iter()
+            .map(|g| g.fitness)
+            .max_by(|a, b| a.partial_cmp(b).unwrap())
+    }
+
+    pub fn update_age(&mut self, generation: u64) {
+        self.age = generation - self.created;
+
+        if let Some(fitness) = self.champion_fitness() {
+            self.fitness_history.push(fitness);
         }
+    }
 
-        let fitness = self.genomes[0].fitness;
+    pub fn update_stagnancy(&mut self) {
+        let fitness = match self.champion_fitness() {
+            Some(fitness) => fitness,
+            None => {
+                self.stagnancy = u32::MAX;
+                return;
+            }
+        };
 
         if fitness <= self.max_fitness {
             self.stagnancy += 1;
@@ -175,12 +236,12 @@ impl Species {
         }
     }
 
-    pub fn cull_lower_half(&mut self) {
+    pub fn cull(&mut self, sets: &Settings) {
         let len = self.genomes.len();
 
         if len > 2 {
-            self.genomes
-                .truncate(if len % 2 == 0 { len / 2 } else { len / 2 + 1 });
+            let survivors = (len as f64 * sets.survival_threshold).ceil() as usize;
+            self.genomes.truncate(survivors.max(1));
         }
     }
 
@@ -188,3 +249,173 @@ impl Species {
         self.genomes.push(gen);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lineage::Mutation;
+
+    #[test]
+    fn offspring_from_zero_fitness() {
+        let mut species = Species::new(Genome::new(3, 2, false), 0, 0);
+        species.add_genome(Genome::new(3, 2, false));
+
+        for strategy in &[
+            SelectionStrategy::Roulette,
+            SelectionStrategy::Tournament(3),
+            SelectionStrategy::Rank,
+            SelectionStrategy::Uniform,
+        ] {
+            let sets = Settings::new(3, 2, 10).selection_strategy(*strategy);
+
+            assert_eq!(species.produce_offspring(5, &sets).len(), 5);
+        }
+    }
+
+    #[test]
+    fn age_adjusts_shared_fitness() {
+        let sets = Settings::new(3, 2, 10)
+            .young_fitness_boost(2.)
+            .old_fitness_penalty(0.5);
+        let mut genome = Genome::new(3, 2, false);
+        genome.fitness = 4.;
+
+        let mut species = Species::new(genome, 0, 0);
+
+        // Sharing starts from the selection score, which is the raw fitness here
+        let shared = |species: &mut Species| {
+            species.genomes[0].adjusted_fitness = 4.;
+            species.fitness_sharing(&sets, 0., 4.);
+            species.genomes[0].adjusted_fitness
+        };
+
+        species.update_age(0);
+        assert_eq!(shared(&mut species), 8.);
+
+        species.update_age(20);
+        assert_eq!(shared(&mut species), 4.);
+
+        species.update_age(31);
+        assert_eq!(shared(&mut species), 2.);
+    }
+
+    #[test]
+    fn cull_follows_survival_threshold() {
+        let mut species = Species::new(Genome::new(3, 2, false), 0, 0);
+        for _ in 0..9 {
+            species.add_genome(Genome::new(3, 2, false));
+        }
+
+        species.cull(&Settings::new(3, 2, 10).survival_threshold(0.3));
+        assert_eq!(species.genomes.len(), 3);
+
+        species.cull(&Settings::new(3, 2, 10).survival_threshold(0.));
+        assert_eq!(species.genomes.len(), 1);
+    }
+
+    #[test]
+    fn representative_strategies() {
+        let genome = |id: u64, weight: f64| {
+            let mut genome = Genome::new(3, 2, false);
+            genome.id = id;
+            genome.conns.iter_mut().for_each(|c| c.weight = weight);
+            genome
+        };
+
+        let mut species = Species::new(genome(0, 0.), 0, 0);
+        species.genomes = vec![genome(1, 2.), genome(2, 0.), genome(3, 1.)];
+
+        let representative = |species: &mut Species, strategy| {
+            species.update_representative(
+                &Settings::new(3, 2, 10).representative_strategy(strategy),
+                &mut DistanceCache::new(),
+            );
+            species.representative().id
+        };
+
+        assert!([1, 2, 3].contains(&representative(
+            &mut species,
+            RepresentativeStrategy::Random
+        )));
+        assert_eq!(
+            representative(&mut species, RepresentativeStrategy::Champion),
+            1
+        );
+        // Genome 3 lies between the others, so its total distance to them is smallest
+        assert_eq!(
+            representative(&mut species, RepresentativeStrategy::Centroid),
+            3
+        );
+    }
+
+    #[test]
+    fn sharing_schemes() {
+        let shared = |sharing, min_fitness, max_fitness| {
+            let mut species = Species::new(Genome::new(3, 2, false), 0, 0);
+            species.genomes = [4., 2., 2., 0.]
+                .iter()
+                .map(|&fitness| {
+                    let mut genome = Genome::new(3, 2, false);
+                    genome.fitness = fitness;
+                    genome.adjusted_fitness = fitness;
+                    genome
+                })
+                .collect();
+
+            let sets = Settings::new(3, 2, 10).fitness_sharing(sharing);
+            species.fitness_sharing(&sets, min_fitness, max_fitness);
+
+            // Sharing never touches the raw fitness
+            let raw = species
+                .genomes
+                .iter()
+                .map(|g| g.fitness)
+                .collect::<Vec<f64>>();
+            assert_eq!(raw, vec![4., 2., 2., 0.]);
+
+            let adjusted = species
+                .genomes
+                .iter()
+                .map(|g| g.adjusted_fitness)
+                .collect::<Vec<f64>>();
+            (adjusted, species.avg_fitness)
+        };
+
+        assert_eq!(
+            shared(FitnessSharing::SpeciesSize, 0., 4.),
+            (vec![1., 0.5, 0.5, 0.], 0.5)
+        );
+        assert_eq!(
+            shared(FitnessSharing::MinSpeciesSize(8), 0., 4.),
+            (vec![0.5, 0.25, 0.25, 0.], 0.25)
+        );
+        assert_eq!(
+            shared(FitnessSharing::MinSpeciesSize(2), 0., 4.),
+            (vec![1., 0.5, 0.5, 0.], 0.5)
+        );
+        assert_eq!(
+            shared(FitnessSharing::Normalized, 0., 8.),
+            (vec![0.5, 0.25, 0.25, 0.], 0.25)
+        );
+        // A fitness range below 1 isn't stretched
+        assert_eq!(
+            shared(FitnessSharing::Normalized, -1., -0.5),
+            (vec![5., 3., 3., 1.], 3.)
+        );
+    }
+
+    #[test]
+    fn mutation_only_offspring_start_clean() {
+        let mut head = Genome::new(3, 2, false);
+        head.id = 7;
+        head.mutations = vec![Mutation::Weights(3)];
+
+        let species = Species::new(head, 0, 0);
+        let sets = Settings::new(3, 2, 10).only_mut_rate(1.);
+
+        for child in species.produce_offspring(5, &sets) {
+            assert_eq!(child.parents, vec![7]);
+            assert!(child.mutations.is_empty());
+        }
+    }
+}
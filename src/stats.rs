@@ -0,0 +1,9 @@
+// Snapshot of the state of a Population after a generation
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub generation: u64,
+    pub best_fitness: f64,
+    pub species: usize,
+    pub stagnancy: u32,
+    pub speciation_threshold: f64,
+}